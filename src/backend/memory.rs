@@ -0,0 +1,224 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use futures::future::{ready, BoxFuture};
+use rand::{distributions::Alphanumeric, Rng};
+use tokio::sync::Notify;
+use tokio::time::{timeout_at, Instant};
+
+use crate::backend::{JobQueue, JobStore, QueuedJob};
+use crate::models::JobResult;
+
+// How long receive waits for a job before returning None
+const RECEIVE_WAIT: Duration = Duration::from_secs(1);
+// Like the RSMQ messages, a job can only be received a second after it was sent
+const SEND_DELAY: Duration = Duration::from_secs(1);
+
+
+// In-process JobQueue and JobStore, for a single binary without Redis.
+// Nothing survives a restart and a received job is never received again
+#[derive(Default)]
+pub struct MemoryBackend {
+    state: Mutex<MemoryState>,
+    job_sent: Notify
+}
+
+#[derive(Default)]
+struct MemoryState {
+    // Sent jobs, in the order they become visible to receive
+    jobs: VecDeque<(Instant, QueuedJob)>,
+    statuses: HashMap<String, i64>,
+    results: HashMap<String, JobResult>,
+    owners: HashMap<String, String>,
+    credentials: HashMap<String, String>,
+    active_jobs: HashMap<String, HashSet<String>>,
+    // {(client, day): requests}
+    daily_requests: HashMap<(String, u64), u64>
+}
+
+impl MemoryBackend {
+    fn with_state<T>(&self, f: impl FnOnce(&mut MemoryState) -> T) -> T {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(&mut state)
+    }
+}
+
+impl JobQueue for MemoryBackend {
+    fn send(&self, message: String) -> BoxFuture<'_, Result<String, String>> {
+        let id: String = rand::thread_rng().sample_iter(&Alphanumeric).take(30).map(char::from).collect();
+        self.with_state(|state| state.jobs.push_back((Instant::now() + SEND_DELAY, QueuedJob { id: id.clone(), message })));
+        self.job_sent.notify_one();
+        Box::pin(ready(Ok(id)))
+    }
+
+    // Waits for a job instead of returning right away, the redis-workers would spin otherwise
+    fn receive(&self) -> BoxFuture<'_, Result<Option<QueuedJob>, String>> {
+        Box::pin(async move {
+            let deadline = Instant::now() + RECEIVE_WAIT;
+            loop {
+                let (job, visible_at) = self.with_state(|state| match state.jobs.front() {
+                    Some((visible_at, _)) if *visible_at <= Instant::now() => (state.jobs.pop_front().map(|(_, job)| job), None),
+                    Some((visible_at, _)) => (None, Some(*visible_at)),
+                    None => (None, None)
+                });
+                if job.is_some() {
+                    return Ok(job);
+                }
+                if Instant::now() >= deadline {
+                    return Ok(None);
+                }
+                let wake_at = visible_at.map_or(deadline, |visible_at| visible_at.min(deadline));
+                let _ = timeout_at(wake_at, self.job_sent.notified()).await;
+            }
+        })
+    }
+
+    fn delete<'a>(&'a self, job_id: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        self.with_state(|state| state.jobs.retain(|(_, job)| job.id != job_id));
+        Box::pin(ready(Ok(())))
+    }
+}
+
+impl JobStore for MemoryBackend {
+    fn status<'a>(&'a self, job_id: &'a str) -> BoxFuture<'a, Result<Option<i64>, String>> {
+        Box::pin(ready(Ok(self.with_state(|state| state.statuses.get(job_id).copied()))))
+    }
+
+    fn set_status<'a>(&'a self, job_id: &'a str, status: i64) -> BoxFuture<'a, Result<(), String>> {
+        self.with_state(|state| state.statuses.insert(job_id.to_string(), status));
+        Box::pin(ready(Ok(())))
+    }
+
+    fn result<'a>(&'a self, job_id: &'a str) -> BoxFuture<'a, Result<Option<JobResult>, String>> {
+        Box::pin(ready(Ok(self.with_state(|state| state.results.get(job_id).cloned()))))
+    }
+
+    fn set_result<'a>(&'a self, job_id: &'a str, result: &'a JobResult) -> BoxFuture<'a, Result<(), String>> {
+        self.with_state(|state| state.results.insert(job_id.to_string(), result.clone()));
+        Box::pin(ready(Ok(())))
+    }
+
+    fn owner<'a>(&'a self, job_id: &'a str) -> BoxFuture<'a, Result<Option<String>, String>> {
+        Box::pin(ready(Ok(self.with_state(|state| state.owners.get(job_id).cloned()))))
+    }
+
+    fn set_owner<'a>(&'a self, job_id: &'a str, client: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        self.with_state(|state| state.owners.insert(job_id.to_string(), client.to_string()));
+        Box::pin(ready(Ok(())))
+    }
+
+    fn delete_job<'a>(&'a self, job_id: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        self.with_state(|state| {
+            state.statuses.remove(job_id);
+            state.results.remove(job_id);
+            state.owners.remove(job_id);
+        });
+        Box::pin(ready(Ok(())))
+    }
+
+    fn credentials<'a>(&'a self, reference: &'a str) -> BoxFuture<'a, Result<Option<String>, String>> {
+        Box::pin(ready(Ok(self.with_state(|state| state.credentials.get(reference).cloned()))))
+    }
+
+    // The jobs never outlive the process, neither do their credentials
+    fn set_credentials<'a>(&'a self, reference: &'a str, sealed: String, _ttl_s: u64) -> BoxFuture<'a, Result<(), String>> {
+        self.with_state(|state| state.credentials.insert(reference.to_string(), sealed));
+        Box::pin(ready(Ok(())))
+    }
+
+    fn delete_credentials<'a>(&'a self, reference: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        self.with_state(|state| state.credentials.remove(reference));
+        Box::pin(ready(Ok(())))
+    }
+
+    // Only the Config's API keys are known without Redis
+    fn api_key_client<'a>(&'a self, _api_key: &'a str) -> BoxFuture<'a, Result<Option<String>, String>> {
+        Box::pin(ready(Ok(None)))
+    }
+
+    fn reserve_active_job<'a>(&'a self, client: &'a str, job_id: &'a str, max_jobs: u32) -> BoxFuture<'a, Result<Option<u32>, String>> {
+        let active_jobs = self.with_state(|state| {
+            let jobs = state.active_jobs.entry(client.to_string()).or_default();
+            if max_jobs > 0 && jobs.len() >= max_jobs as usize {
+                return None;
+            }
+            jobs.insert(job_id.to_string());
+            Some(jobs.len() as u32)
+        });
+        Box::pin(ready(Ok(active_jobs)))
+    }
+
+    fn remove_active_job<'a>(&'a self, client: &'a str, job_id: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        self.with_state(|state| {
+            if let Some(jobs) = state.active_jobs.get_mut(client) {
+                jobs.remove(job_id);
+            }
+        });
+        Box::pin(ready(Ok(())))
+    }
+
+    fn reserve_daily_requests<'a>(&'a self, client: &'a str, day: u64, limit: u64, share: u64) -> BoxFuture<'a, Result<Option<u64>, String>> {
+        let reserved = self.with_state(|state| {
+            let used = state.daily_requests.entry((client.to_string(), day)).or_insert(0);
+            if *used >= limit {
+                return None;
+            }
+            let reserved = (limit - *used).div_ceil(share);
+            *used += reserved;
+            Some(reserved)
+        });
+        Box::pin(ready(Ok(reserved)))
+    }
+
+    // The counters of the days before yesterday are dropped, as they expire in Redis
+    fn add_daily_requests<'a>(&'a self, client: &'a str, day: u64, requests: i64) -> BoxFuture<'a, Result<(), String>> {
+        self.with_state(|state| {
+            state.daily_requests.retain(|(_, d), _| *d + 1 >= day);
+            let used = state.daily_requests.entry((client.to_string(), day)).or_insert(0);
+            *used = used.saturating_add_signed(requests);
+        });
+        Box::pin(ready(Ok(())))
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::MemoryBackend;
+    use crate::backend::{JobQueue, JobStore};
+
+
+    #[tokio::test]
+    async fn test_memory_backend() {
+        let backend = MemoryBackend::default();
+        let job_id = backend.send(String::from("{}")).await.unwrap();
+        backend.set_owner(job_id.as_str(), "team-a").await.unwrap();
+        backend.set_status(job_id.as_str(), -1).await.unwrap();
+        assert_eq!(backend.reserve_active_job("team-a", job_id.as_str(), 1).await.unwrap(), Some(1));
+        assert_eq!(backend.reserve_active_job("team-a", "job-2", 1).await.unwrap(), None);
+
+        let job = backend.receive().await.unwrap().expect("visible after SEND_DELAY");
+        assert_eq!(job.id, job_id);
+        assert_eq!(job.message, "{}");
+        assert_eq!(backend.status(job_id.as_str()).await.unwrap(), Some(-1));
+        assert_eq!(backend.owner(job_id.as_str()).await.unwrap().as_deref(), Some("team-a"));
+
+        backend.remove_active_job("team-a", job_id.as_str()).await.unwrap();
+        assert_eq!(backend.reserve_active_job("team-a", "job-2", 1).await.unwrap(), Some(1));
+        backend.add_daily_requests("team-a", 10, 500).await.unwrap();
+        backend.add_daily_requests("team-a", 12, 20).await.unwrap();
+        // The day 10 counter is gone by day 12
+        assert_eq!(backend.reserve_daily_requests("team-a", 10, 1000, 1).await.unwrap(), Some(1000));
+        assert_eq!(backend.reserve_daily_requests("team-a", 12, 1000, 2).await.unwrap(), Some(490));
+        backend.add_daily_requests("team-a", 12, -90).await.unwrap();
+        assert_eq!(backend.reserve_daily_requests("team-a", 12, 1000, 1).await.unwrap(), Some(580));
+        assert_eq!(backend.reserve_daily_requests("team-a", 12, 1000, 1).await.unwrap(), None);
+
+        backend.delete_job(job_id.as_str()).await.unwrap();
+        assert_eq!(backend.status(job_id.as_str()).await.unwrap(), None);
+        assert_eq!(backend.owner(job_id.as_str()).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,102 @@
+pub mod memory;
+pub mod redis;
+
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+
+use crate::backend::memory::MemoryBackend;
+use crate::backend::redis::{RedisOptions, RedisPool};
+use crate::config::Config;
+use crate::models::JobResult;
+
+
+// A job as received from the JobQueue, message is the serialized TodoJob
+#[derive(Clone, Debug)]
+pub struct QueuedJob {
+    pub id: String,
+    pub message: String
+}
+
+// Delivers the jobs from the API to the redis-workers
+pub trait JobQueue: Send + Sync {
+    // Returns the new job's id. The job can only be received after a short delay,
+    // so the API marks it as pending before a redis-worker gets it
+    fn send(&self, message: String) -> BoxFuture<'_, Result<String, String>>;
+
+    // None when no job showed up for a while. A received job is only removed from the queue
+    // by delete, depending on the backend it may be received again in the meantime
+    fn receive(&self) -> BoxFuture<'_, Result<Option<QueuedJob>, String>>;
+
+    fn delete<'a>(&'a self, job_id: &'a str) -> BoxFuture<'a, Result<(), String>>;
+}
+
+// Everything the API and the redis-workers share about the jobs and the clients
+pub trait JobStore: Send + Sync {
+    // -1 => pending, 0 => allocated to a redis-worker, -2 => errored, >0 => rps of the finished job
+    fn status<'a>(&'a self, job_id: &'a str) -> BoxFuture<'a, Result<Option<i64>, String>>;
+
+    fn set_status<'a>(&'a self, job_id: &'a str, status: i64) -> BoxFuture<'a, Result<(), String>>;
+
+    fn result<'a>(&'a self, job_id: &'a str) -> BoxFuture<'a, Result<Option<JobResult>, String>>;
+
+    fn set_result<'a>(&'a self, job_id: &'a str, result: &'a JobResult) -> BoxFuture<'a, Result<(), String>>;
+
+    // Client that submitted the job
+    fn owner<'a>(&'a self, job_id: &'a str) -> BoxFuture<'a, Result<Option<String>, String>>;
+
+    fn set_owner<'a>(&'a self, job_id: &'a str, client: &'a str) -> BoxFuture<'a, Result<(), String>>;
+
+    // Deletes the job's status, result and owner, once the client read them
+    fn delete_job<'a>(&'a self, job_id: &'a str) -> BoxFuture<'a, Result<(), String>>;
+
+    // The job's JobCredentials, sealed by the API's SecretBox
+    fn credentials<'a>(&'a self, reference: &'a str) -> BoxFuture<'a, Result<Option<String>, String>>;
+
+    // Deleted after ttl_s seconds, should the job never be finished
+    fn set_credentials<'a>(&'a self, reference: &'a str, sealed: String, ttl_s: u64) -> BoxFuture<'a, Result<(), String>>;
+
+    fn delete_credentials<'a>(&'a self, reference: &'a str) -> BoxFuture<'a, Result<(), String>>;
+
+    // Client of an API key registered at runtime, the Config's API keys aren't stored
+    fn api_key_client<'a>(&'a self, api_key: &'a str) -> BoxFuture<'a, Result<Option<String>, String>>;
+
+    // Adds the job to the client's pending and running jobs unless it already has max_jobs of them
+    // (0 => unlimited), in one atomic step. Returns the client's active jobs, this one included, None when full
+    fn reserve_active_job<'a>(&'a self, client: &'a str, job_id: &'a str, max_jobs: u32) -> BoxFuture<'a, Result<Option<u32>, String>>;
+
+    fn remove_active_job<'a>(&'a self, client: &'a str, job_id: &'a str) -> BoxFuture<'a, Result<(), String>>;
+
+    // Requests reserved (then sent) by the client's jobs on a given day, see ClientUsage::today.
+    // Counts 1/share of the requests left below the daily limit as used, in one atomic step.
+    // Returns the reserved requests, None when the limit is reached
+    fn reserve_daily_requests<'a>(&'a self, client: &'a str, day: u64, limit: u64, share: u64) -> BoxFuture<'a, Result<Option<u64>, String>>;
+
+    // Negative requests give back reserved ones that weren't sent
+    fn add_daily_requests<'a>(&'a self, client: &'a str, day: u64, requests: i64) -> BoxFuture<'a, Result<(), String>>;
+}
+
+// The JobQueue and JobStore selected by Config's QUEUE_BACKEND
+#[derive(Clone)]
+pub struct Backend {
+    pub queue: Arc<dyn JobQueue>,
+    pub store: Arc<dyn JobStore>
+}
+
+impl Backend {
+    pub async fn from_config(config: &Config) -> Result<Backend, String> {
+        match config.queue_backend.as_str() {
+            "redis" => {
+                // The jobs left by the previous run are reset on the first call that reaches Redis
+                let pool = Arc::new(RedisPool::new(RedisOptions::from_config(config)?).map_err(|e| e.to_string())?);
+                Ok(Backend { queue: pool.clone(), store: pool })
+            },
+            // Single process, no Redis: the API and the redis-workers share the jobs in memory
+            "memory" => {
+                let memory = Arc::new(MemoryBackend::default());
+                Ok(Backend { queue: memory.clone(), store: memory })
+            },
+            other => Err(format!("Unknown QUEUE_BACKEND {}, expected redis or memory", other))
+        }
+    }
+}
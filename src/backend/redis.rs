@@ -0,0 +1,402 @@
+use std::fmt;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, Client, ConnectionAddr, ConnectionInfo, RedisError, Script};
+use rsmq_async::{Rsmq, RsmqConnection, RsmqError, RsmqMessage, RsmqOptions};
+use tokio::sync::{Mutex, OnceCell};
+
+use crate::backend::{JobQueue, JobStore, QueuedJob};
+use crate::config::Config;
+use crate::models::{ClientUsage, JobCredentials, JobResult};
+
+// Hash of the API keys managed at runtime: {api_key: client_name}
+pub const API_KEYS_REDIS_KEY: &str = "api_keys";
+
+// SCARD then SADD, -1 when the set already has ARGV[2] members (0 => unlimited)
+const RESERVE_ACTIVE_JOB_SCRIPT: &str = r"
+local active_jobs = redis.call('SCARD', KEYS[1])
+if tonumber(ARGV[2]) > 0 and active_jobs >= tonumber(ARGV[2]) then
+    return -1
+end
+redis.call('SADD', KEYS[1], ARGV[1])
+return active_jobs + 1
+";
+
+// INCRBY of 1/ARGV[2] of the requests left below the limit ARGV[1], -1 when none are left
+const RESERVE_DAILY_REQUESTS_SCRIPT: &str = r"
+local used = tonumber(redis.call('GET', KEYS[1]) or '0')
+local limit = tonumber(ARGV[1])
+if used >= limit then
+    return -1
+end
+local reserved = math.ceil((limit - used) / tonumber(ARGV[2]))
+redis.call('INCRBY', KEYS[1], reserved)
+redis.call('EXPIRE', KEYS[1], 172800)
+return reserved
+";
+
+// Idle RSMQ connections kept for the next requests, the others are closed once used
+const MAX_IDLE_RSMQ_CONNECTIONS: usize = 16;
+
+
+// How the API and the redis-workers connect to Redis, and where they keep their keys
+#[derive(Clone)]
+pub struct RedisOptions {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub tls: bool,
+    pub db: i64,
+    pub key_prefix: String,
+    pub queue_name: String
+}
+
+impl RedisOptions {
+    pub fn from_config(config: &Config) -> Result<RedisOptions, String> {
+        let non_empty = |value: &Option<String>| value.clone().filter(|v| !v.is_empty());
+        Ok(RedisOptions {
+            host: config.redis_address.clone(),
+            port: config.redis_port.parse().map_err(|e| format!("Invalid REDIS_PORT {}: {}", config.redis_port, e))?,
+            username: non_empty(&config.redis_username),
+            password: non_empty(&config.redis_password),
+            tls: config.redis_tls,
+            db: config.redis_db,
+            key_prefix: config.redis_key_prefix.clone(),
+            queue_name: config.redis_queue_name.clone()
+        })
+    }
+
+    pub fn client(&self) -> Result<Client, RedisError> {
+        let addr = match self.tls {
+            true => ConnectionAddr::TcpTls { host: self.host.clone(), port: self.port, insecure: false },
+            false => ConnectionAddr::Tcp(self.host.clone(), self.port)
+        };
+        Client::open(ConnectionInfo {
+            addr: Box::new(addr),
+            db: self.db,
+            username: self.username.clone(),
+            passwd: self.password.clone()
+        })
+    }
+
+    // RSMQ's own keys go under the key prefix as well
+    pub fn rsmq_options(&self) -> RsmqOptions {
+        RsmqOptions {
+            host: self.host.clone(),
+            port: self.port.to_string(),
+            db: self.db as u8,
+            realtime: false,
+            password: self.password.clone(),
+            ns: format!("{}rsmq", self.key_prefix)
+        }
+    }
+
+    // Every key the service reads or writes goes through here
+    pub fn key(&self, key: &str) -> String {
+        format!("{}{}", self.key_prefix, key)
+    }
+}
+
+// Keeps the password out of the logs
+impl fmt::Debug for RedisOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisOptions")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("username", &self.username)
+            .field("tls", &self.tls)
+            .field("db", &self.db)
+            .field("key_prefix", &self.key_prefix)
+            .field("queue_name", &self.queue_name)
+            .finish()
+    }
+}
+
+// Redis connections shared by the API and the redis-workers, both kinds get back on their
+// feet when Redis drops: the ConnectionManager reconnects on its own and the RSMQ
+// connections are dropped when they fail, a new one is opened by the next call
+#[derive(Clone)]
+pub struct RedisPool {
+    pub options: RedisOptions,
+    client: Client,
+    // Created on first use, so the API starts even while Redis is down
+    connection_manager: Arc<Mutex<Option<ConnectionManager>>>,
+    // Rsmq needs a connection of its own for every concurrent call
+    rsmq: Arc<Mutex<Vec<Rsmq>>>,
+    // Set once the jobs left by the previous run are reset, which is retried until Redis is up
+    jobs_reset: Arc<OnceCell<()>>
+}
+
+impl RedisPool {
+    pub fn new(options: RedisOptions) -> Result<RedisPool, RedisError> {
+        Ok(RedisPool {
+            client: options.client()?,
+            options,
+            connection_manager: Arc::new(Mutex::new(None)),
+            rsmq: Arc::new(Mutex::new(Vec::new())),
+            jobs_reset: Arc::new(OnceCell::new())
+        })
+    }
+
+    // Every call goes through here: nothing is read or written before the jobs are reset
+    async fn ready(&self) -> Result<(), String> {
+        self.jobs_reset.get_or_try_init(|| self.reset_jobs()).await.map(|_| ())
+    }
+
+    // ConnectionManager is a cheap handle to one multiplexed connection
+    async fn connection_manager(&self) -> Result<ConnectionManager, String> {
+        self.ready().await?;
+        self.open_connection_manager().await
+    }
+
+    async fn open_connection_manager(&self) -> Result<ConnectionManager, String> {
+        let mut connection_manager = self.connection_manager.lock().await;
+        if let Some(c) = connection_manager.as_ref() {
+            return Ok(c.clone());
+        }
+        let c = ConnectionManager::new(self.client.clone()).await.map_err(|e| e.to_string())?;
+        *connection_manager = Some(c.clone());
+        Ok(c)
+    }
+
+    pub fn key(&self, key: &str) -> String {
+        self.options.key(key)
+    }
+
+    // Empties the job queue. The jobs queued or running before are gone with it,
+    // so they are also removed from the clients' active jobs and their credentials are deleted
+    async fn reset_jobs(&self) -> Result<(), String> {
+        let mut rsmq = self.open_rsmq().await.map_err(|e| e.to_string())?;
+        let _ = rsmq.delete_queue(self.options.queue_name.as_str()).await;
+        let result = rsmq.create_queue(self.options.queue_name.as_str(), None, None, None).await;
+        self.release(rsmq, &result).await;
+        result.map_err(|e| e.to_string())?;
+
+        self.delete_matching(&ClientUsage::active_jobs_key("*")).await?;
+        self.delete_matching(&JobCredentials::redis_key("*")).await
+    }
+
+    async fn delete_matching(&self, pattern: &str) -> Result<(), String> {
+        let mut c = self.open_connection_manager().await?;
+        let mut matching_keys: Vec<String> = Vec::new();
+        {
+            let mut keys = c.scan_match::<_, String>(self.key(pattern)).await.map_err(|e| e.to_string())?;
+            while let Some(key) = keys.next_item().await {
+                matching_keys.push(key);
+            }
+        }
+        if matching_keys.is_empty() {
+            return Ok(());
+        }
+        c.del(matching_keys).await.map_err(|e| e.to_string())
+    }
+
+    async fn rsmq(&self) -> Result<Rsmq, String> {
+        self.ready().await?;
+        self.open_rsmq().await.map_err(|e| e.to_string())
+    }
+
+    async fn open_rsmq(&self) -> Result<Rsmq, RsmqError> {
+        let idle = self.rsmq.lock().await.pop();
+        match idle {
+            Some(r) => Ok(r),
+            None => Ok(Rsmq::new_with_connection(self.options.rsmq_options(), self.client.get_async_connection().await?))
+        }
+    }
+
+    // A connection that failed on Redis' side may be broken, it isn't reused
+    async fn release<T>(&self, rsmq: Rsmq, result: &Result<T, RsmqError>) {
+        if !matches!(result, Err(RsmqError::RedisError(_))) {
+            let mut idle = self.rsmq.lock().await;
+            if idle.len() < MAX_IDLE_RSMQ_CONNECTIONS {
+                idle.push(rsmq);
+            }
+        }
+    }
+}
+
+impl JobQueue for RedisPool {
+    fn send(&self, message: String) -> BoxFuture<'_, Result<String, String>> {
+        Box::pin(async move {
+            let mut rsmq = self.rsmq().await?;
+            let result = rsmq.send_message(self.options.queue_name.as_str(), message, Some(1)).await;
+            self.release(rsmq, &result).await;
+            result.map_err(|e| e.to_string())
+        })
+    }
+
+    // The message stays hidden for the queue's visibility timeout, then it is received again
+    // unless deleted, which is why the redis-workers check the job's status first
+    fn receive(&self) -> BoxFuture<'_, Result<Option<QueuedJob>, String>> {
+        Box::pin(async move {
+            let mut rsmq = self.rsmq().await?;
+            let result: Result<Option<RsmqMessage<String>>, RsmqError> = rsmq.receive_message(self.options.queue_name.as_str(), None).await;
+            self.release(rsmq, &result).await;
+            Ok(result.map_err(|e| e.to_string())?.map(|m| QueuedJob { id: m.id, message: m.message }))
+        })
+    }
+
+    fn delete<'a>(&'a self, job_id: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let mut rsmq = self.rsmq().await?;
+            let result = rsmq.delete_message(self.options.queue_name.as_str(), job_id).await;
+            self.release(rsmq, &result).await;
+            result.map(|_| ()).map_err(|e| e.to_string())
+        })
+    }
+}
+
+// Keys: {job_id} => status, {job_id}:result, credentials:{reference}, api_keys hash,
+// quota:{client}:active_jobs set and quota:{client}:requests:{day}, all under the key prefix
+impl JobStore for RedisPool {
+    fn status<'a>(&'a self, job_id: &'a str) -> BoxFuture<'a, Result<Option<i64>, String>> {
+        Box::pin(async move {
+            let mut c = self.connection_manager().await?;
+            c.get(self.key(job_id)).await.map_err(|e| e.to_string())
+        })
+    }
+
+    fn set_status<'a>(&'a self, job_id: &'a str, status: i64) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let mut c = self.connection_manager().await?;
+            c.set(self.key(job_id), status).await.map_err(|e| e.to_string())
+        })
+    }
+
+    fn result<'a>(&'a self, job_id: &'a str) -> BoxFuture<'a, Result<Option<JobResult>, String>> {
+        Box::pin(async move {
+            let mut c = self.connection_manager().await?;
+            let result: Option<String> = c.get(self.key(&JobResult::redis_key(job_id))).await.map_err(|e| e.to_string())?;
+            result.map(|r| serde_json::from_str(r.as_str()).map_err(|e| e.to_string())).transpose()
+        })
+    }
+
+    fn set_result<'a>(&'a self, job_id: &'a str, result: &'a JobResult) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let mut c = self.connection_manager().await?;
+            let result = serde_json::to_string(result).map_err(|e| e.to_string())?;
+            c.set(self.key(&JobResult::redis_key(job_id)), result).await.map_err(|e| e.to_string())
+        })
+    }
+
+    fn owner<'a>(&'a self, job_id: &'a str) -> BoxFuture<'a, Result<Option<String>, String>> {
+        Box::pin(async move {
+            let mut c = self.connection_manager().await?;
+            c.get(self.key(&ClientUsage::job_owner_key(job_id))).await.map_err(|e| e.to_string())
+        })
+    }
+
+    fn set_owner<'a>(&'a self, job_id: &'a str, client: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let mut c = self.connection_manager().await?;
+            c.set(self.key(&ClientUsage::job_owner_key(job_id)), client).await.map_err(|e| e.to_string())
+        })
+    }
+
+    fn delete_job<'a>(&'a self, job_id: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let mut c = self.connection_manager().await?;
+            c.del(&[self.key(job_id), self.key(&JobResult::redis_key(job_id)), self.key(&ClientUsage::job_owner_key(job_id))]).await.map_err(|e| e.to_string())
+        })
+    }
+
+    fn credentials<'a>(&'a self, reference: &'a str) -> BoxFuture<'a, Result<Option<String>, String>> {
+        Box::pin(async move {
+            let mut c = self.connection_manager().await?;
+            c.get(self.key(&JobCredentials::redis_key(reference))).await.map_err(|e| e.to_string())
+        })
+    }
+
+    fn set_credentials<'a>(&'a self, reference: &'a str, sealed: String, ttl_s: u64) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let mut c = self.connection_manager().await?;
+            c.set_ex(self.key(&JobCredentials::redis_key(reference)), sealed, ttl_s as usize).await.map_err(|e| e.to_string())
+        })
+    }
+
+    fn delete_credentials<'a>(&'a self, reference: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let mut c = self.connection_manager().await?;
+            c.del(self.key(&JobCredentials::redis_key(reference))).await.map_err(|e| e.to_string())
+        })
+    }
+
+    fn api_key_client<'a>(&'a self, api_key: &'a str) -> BoxFuture<'a, Result<Option<String>, String>> {
+        Box::pin(async move {
+            let mut c = self.connection_manager().await?;
+            c.hget(self.key(API_KEYS_REDIS_KEY), api_key).await.map_err(|e| e.to_string())
+        })
+    }
+
+    fn reserve_active_job<'a>(&'a self, client: &'a str, job_id: &'a str, max_jobs: u32) -> BoxFuture<'a, Result<Option<u32>, String>> {
+        Box::pin(async move {
+            let mut c = self.connection_manager().await?;
+            let active_jobs: i64 = Script::new(RESERVE_ACTIVE_JOB_SCRIPT)
+                .key(self.key(&ClientUsage::active_jobs_key(client)))
+                .arg(job_id)
+                .arg(max_jobs)
+                .invoke_async(&mut c).await.map_err(|e| e.to_string())?;
+            Ok(u32::try_from(active_jobs).ok())
+        })
+    }
+
+    fn remove_active_job<'a>(&'a self, client: &'a str, job_id: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let mut c = self.connection_manager().await?;
+            c.srem(self.key(&ClientUsage::active_jobs_key(client)), job_id).await.map_err(|e| e.to_string())
+        })
+    }
+
+    fn reserve_daily_requests<'a>(&'a self, client: &'a str, day: u64, limit: u64, share: u64) -> BoxFuture<'a, Result<Option<u64>, String>> {
+        Box::pin(async move {
+            let mut c = self.connection_manager().await?;
+            let requests: i64 = Script::new(RESERVE_DAILY_REQUESTS_SCRIPT)
+                .key(self.key(&ClientUsage::daily_requests_key(client, day)))
+                .arg(limit)
+                .arg(share)
+                .invoke_async(&mut c).await.map_err(|e| e.to_string())?;
+            Ok(u64::try_from(requests).ok())
+        })
+    }
+
+    // The counters expire once the day they count is over
+    fn add_daily_requests<'a>(&'a self, client: &'a str, day: u64, requests: i64) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let mut c = self.connection_manager().await?;
+            let key = self.key(&ClientUsage::daily_requests_key(client, day));
+            let _: i64 = c.incr(key.as_str(), requests).await.map_err(|e| e.to_string())?;
+            c.expire(key.as_str(), 2 * 86400).await.map_err(|e| e.to_string())
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::RedisOptions;
+
+
+    #[test]
+    fn test_keys_and_queue_are_namespaced() {
+        let options = RedisOptions {
+            host: String::from("redis.internal"),
+            port: 6380,
+            username: Some(String::from("benchmarking")),
+            password: Some(String::from("secret")),
+            tls: true,
+            db: 2,
+            key_prefix: String::from("bench:"),
+            queue_name: String::from("jobs_q")
+        };
+        assert_eq!(options.key("api_keys"), "bench:api_keys");
+        assert_eq!(options.rsmq_options().ns, "bench:rsmq");
+        assert_eq!(options.rsmq_options().db, 2);
+        assert!(options.client().is_ok());
+        assert!(!format!("{:?}", options).contains("secret"));
+    }
+}
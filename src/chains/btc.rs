@@ -0,0 +1,145 @@
+use futures::future::BoxFuture;
+use reqwest;
+use serde_json::json;
+
+use crate::chains::{ChainProfile, json_rpc_response_is_ok, push_seeded_methods, rpc_call, push_unique, MAX_SEED_VALUES, SEED_BLOCKS};
+use crate::models::{ChainSeed, JsonRpcMethod, TodoJob};
+
+
+// Bitcoin Core compatible nodes
+pub struct Btc;
+
+impl ChainProfile for Btc {
+    fn workload(&self, seed: &ChainSeed, _job: &TodoJob) -> Vec<JsonRpcMethod> {
+        let mut btc_methods = vec![
+            JsonRpcMethod {
+                payload:
+                    r#"{
+                        "jsonrpc": "2.0",
+                        "id": "1",
+                        "method": "sendrawtransaction",
+                        "params": ["01000000010b4d12cf890540c116463510fa823188a648ce7539b6a9ceb454bfbe8da447d7230000006b48304502210095d4cf3d7dcffaf50354ad3fd6e909e6c81156ac8f26b4a972c178e1c6b886b802206c6d3287d2a1bd9aa9f16187bf49ec24581d2b471e222d24babfd511d83bf29601210242581ee416579a142b436a2ef5ef0e117941fe7a2998d2d34c9f476233080f48ffffffff02a6580100000000001976a91476c37e0cc46f856092164f2fad78dbfc7de8c87e88ac3fc30f000000000017a91422603b24d6bc97d390793ec58de38222fcccae328700000000"]
+                    }"#.into(),
+                weight: 16
+            },
+            JsonRpcMethod {
+                payload:
+                    r#"{
+                        "jsonrpc": "2.0",
+                        "id": "1",
+                        "method": "logging",
+                        "params": [["all"], ["libevent"]]
+                    }"#.into(),
+                weight: 252
+            }
+        ];
+        push_seeded_methods(
+            &mut btc_methods,
+            &seed.outpoints,
+            r#"{
+                "jsonrpc": "2.0",
+                "id": "1",
+                "method": "gettxout",
+                "params": ["47df2d439a7f7156da11a01478ea921c9fabc0f55a9f901291dccc762b40a937", 1]
+            }"#,
+            255,
+            |(txid, vout)| format!(r#"{{"jsonrpc": "2.0", "id": "1", "method": "gettxout", "params": ["{}", {}]}}"#, txid, vout)
+        );
+        push_seeded_methods(
+            &mut btc_methods,
+            &seed.block_hashes,
+            r#"{
+                "jsonrpc": "2.0",
+                "id": "1",
+                "method": "getblock", 
+                "params": ["00000000c937983704a73af28acdec37b049d214adbda81d7e2a3dd146f6ed09"]
+            }"#,
+            333,
+            |hash| format!(r#"{{"jsonrpc": "2.0", "id": "1", "method": "getblock", "params": ["{}"]}}"#, hash)
+        );
+        push_seeded_methods(
+            &mut btc_methods,
+            &seed.block_numbers,
+            r#"{
+                "jsonrpc": "2.0",
+                "id": "1",
+                "method": "getblockstats",
+                "params": [103221, []]
+            }"#,
+            390,
+            |height| format!(r#"{{"jsonrpc": "2.0", "id": "1", "method": "getblockstats", "params": [{}, []]}}"#, height)
+        );
+
+        btc_methods
+    }
+
+    fn seed<'a>(&'a self, client: &'a reqwest::Client, job: &'a TodoJob) -> BoxFuture<'a, Option<ChainSeed>> {
+        Box::pin(seed_from_node(client, job))
+    }
+
+    fn health_probe(&self) -> &'static str {
+        r#"{"jsonrpc": "2.0", "id": "1", "method": "getblockcount", "params": []}"#
+    }
+
+    fn validates_response_body(&self) -> bool {
+        // Nodes answering JSON-RPC 2.0 requests report errors inside HTTP 200 responses
+        true
+    }
+
+    // e.g. -28 => node still warming up, -32601 => method disabled by the provider are fails
+    fn response_is_ok(&self, body: &str) -> bool {
+        json_rpc_response_is_ok(body, |code| BTC_EXPECTED_ERROR_CODES.contains(&code))
+    }
+}
+
+// Bitcoin Core RPC error codes that are a valid answer from a healthy node:
+// -5 => block or transaction not found (the workload's default values on another network)
+// -8 => invalid parameter, e.g. a getblockstats height above the node's tip
+// -25, -26, -27 => the workload's sendrawtransaction rejected: missing inputs, rejected, already in chain
+const BTC_EXPECTED_ERROR_CODES: [i64; 5] = [-5, -8, -25, -26, -27];
+
+async fn seed_from_node(
+    client: &reqwest::Client,
+    job: &TodoJob
+) -> Option<ChainSeed> {
+    let mut seed = ChainSeed::default();
+    let info = rpc_call(client, job, "getblockchaininfo", json!([])).await?;
+    let mut block_hash = info["bestblockhash"].as_str()?.to_string();
+
+    for _i in 0..SEED_BLOCKS {
+        // Verbosity 2 returns the decoded transactions, including their outputs
+        let block = match rpc_call(client, job, "getblock", json!([block_hash, 2])).await {
+            Some(b) => b,
+            None => break
+        };
+        if let Some(height) = block["height"].as_u64() {
+            seed.block_numbers.push(height);
+        }
+        seed.block_hashes.push(block_hash.clone());
+        for tx in block["tx"].as_array().into_iter().flatten() {
+            let txid = match tx["txid"].as_str() {
+                Some(t) => t,
+                None => continue
+            };
+            push_unique(&mut seed.tx_hashes, txid);
+            for vout in tx["vout"].as_array().into_iter().flatten() {
+                if let Some(n) = vout["n"].as_u64() {
+                    if seed.outpoints.len() < MAX_SEED_VALUES {
+                        seed.outpoints.push((txid.to_string(), n as u32));
+                    }
+                }
+                if let Some(address) = vout["scriptPubKey"]["address"].as_str() {
+                    push_unique(&mut seed.addresses, address);
+                }
+            }
+        }
+        block_hash = match block["previousblockhash"].as_str() {
+            Some(h) => h.to_string(),
+            None => break
+        };
+    }
+    if seed.block_hashes.is_empty() {
+        return None;
+    }
+    Some(seed)
+}
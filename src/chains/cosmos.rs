@@ -0,0 +1,156 @@
+use futures::future::BoxFuture;
+use reqwest;
+use serde_json::json;
+
+use crate::chains::{ChainProfile, json_rpc_value_is_ok, push_seeded_methods, rpc_call, push_unique, SEED_BLOCKS};
+use crate::models::{ChainSeed, JsonRpcMethod, TodoJob, RpcPayload};
+
+
+// Cosmos SDK chains: Tendermint RPC and, optionally, LCD REST
+pub struct Cosmos;
+
+impl ChainProfile for Cosmos {
+    fn workload(&self, seed: &ChainSeed, job: &TodoJob) -> Vec<JsonRpcMethod> {
+        let mut cosmos_methods = vec![
+            JsonRpcMethod {
+                payload:
+                    r#"{
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "method": "abci_query",
+                        "params": {"path": "/cosmos.base.tendermint.v1beta1.Service/GetNodeInfo", "data": ""}
+                    }"#.into(),
+                weight: 88
+            }
+        ];
+        push_seeded_methods(
+            &mut cosmos_methods,
+            &seed.block_numbers,
+            r#"{
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tx_search",
+                "params": {"query": "tx.height=5200791", "page": "1", "per_page": "10"}
+            }"#,
+            252,
+            |height| format!(r#"{{"jsonrpc": "2.0", "id": 1, "method": "tx_search", "params": {{"query": "tx.height={}", "page": "1", "per_page": "10"}}}}"#, height)
+        );
+        cosmos_methods.push(
+            JsonRpcMethod {
+                payload:
+                    r#"{
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "method": "status",
+                        "params": {}
+                    }"#.into(),
+                weight: 333
+            }
+        );
+        // Tendermint URI call, GET {endpoint_url}/block?height={height}
+        push_seeded_methods(
+            &mut cosmos_methods,
+            &seed.block_numbers,
+            RpcPayload::Get(String::from("/block")),
+            399,
+            |height| RpcPayload::Get(format!("/block?height={}", height))
+        );
+
+        // Cosmos LCD REST calls are only sent when the job provides the REST endpoint
+        if job.rest_endpoint_url.is_none() {
+            return cosmos_methods;
+        }
+        cosmos_methods.push(
+            JsonRpcMethod {
+                payload: RpcPayload::RestGet(String::from("/cosmos/base/tendermint/v1beta1/blocks/latest")),
+                weight: 255
+            }
+        );
+        cosmos_methods.push(
+            JsonRpcMethod {
+                payload: RpcPayload::RestGet(String::from("/cosmos/staking/v1beta1/validators?pagination.limit=10")),
+                weight: 88
+            }
+        );
+        push_seeded_methods(
+            &mut cosmos_methods,
+            &seed.tx_hashes,
+            RpcPayload::RestGet(String::from("/cosmos/tx/v1beta1/txs?events=tx.height%3D5200791")),
+            390,
+            |hash| RpcPayload::RestGet(format!("/cosmos/tx/v1beta1/txs/{}", hash))
+        );
+        push_seeded_methods(
+            &mut cosmos_methods,
+            &seed.addresses,
+            RpcPayload::RestGet(String::from("/cosmos/bank/v1beta1/supply?pagination.limit=10")),
+            545,
+            |address| RpcPayload::RestGet(format!("/cosmos/bank/v1beta1/balances/{}", address))
+        );
+        cosmos_methods
+    }
+
+    fn seed<'a>(&'a self, client: &'a reqwest::Client, job: &'a TodoJob) -> BoxFuture<'a, Option<ChainSeed>> {
+        Box::pin(seed_from_node(client, job))
+    }
+
+    fn health_probe(&self) -> &'static str {
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "health", "params": {}}"#
+    }
+
+    fn validates_response_body(&self) -> bool {
+        // Tendermint reports errors inside HTTP 200 JSON-RPC responses
+        true
+    }
+
+    // Every Tendermint JSON-RPC error is a fail (e.g. -32603 => internal error, pruned height).
+    // LCD REST bodies aren't JSON-RPC, their errors come with an HTTP error status
+    fn response_is_ok(&self, body: &str) -> bool {
+        let response: serde_json::Value = match serde_json::from_str(body) {
+            Ok(r) => r,
+            Err(_) => return false
+        };
+        if response.get("jsonrpc").is_none() {
+            return true;
+        }
+        json_rpc_value_is_ok(&response, |_| false)
+    }
+}
+
+async fn seed_from_node(
+    client: &reqwest::Client,
+    job: &TodoJob
+) -> Option<ChainSeed> {
+    let mut seed = ChainSeed::default();
+    let status = rpc_call(client, job, "status", json!({})).await?;
+    // Tendermint encodes heights as strings
+    let latest: u64 = status["sync_info"]["latest_block_height"].as_str()?.parse().ok()?;
+
+    for height in (latest.saturating_sub(SEED_BLOCKS - 1)..=latest).rev() {
+        let txs = match rpc_call(client, job, "tx_search", json!({
+            "query": format!("tx.height={}", height),
+            "page": "1",
+            "per_page": "20"
+        })).await {
+            Some(t) => t,
+            None => continue
+        };
+        seed.block_numbers.push(height);
+        for tx in txs["txs"].as_array().into_iter().flatten() {
+            if let Some(hash) = tx["hash"].as_str() {
+                push_unique(&mut seed.tx_hashes, hash);
+            }
+            for event in tx["tx_result"]["events"].as_array().into_iter().flatten() {
+                for attribute in event["attributes"].as_array().into_iter().flatten() {
+                    let is_account = matches!(attribute["key"].as_str(), Some("sender") | Some("receiver") | Some("spender"));
+                    if let (true, Some(address)) = (is_account, attribute["value"].as_str()) {
+                        push_unique(&mut seed.addresses, address);
+                    }
+                }
+            }
+        }
+    }
+    if seed.block_numbers.is_empty() {
+        return None;
+    }
+    Some(seed)
+}
@@ -0,0 +1,215 @@
+use futures::future::BoxFuture;
+use reqwest;
+use serde_json::json;
+
+use crate::chains::{ChainProfile, json_rpc_response_is_ok, push_seeded_methods, rpc_call, push_unique, SEED_BLOCKS};
+use crate::models::{ChainSeed, JsonRpcMethod, TodoJob};
+
+
+// Ethereum and EVM compatible chains (Polygon, BSC, Avalanche C-Chain, ...)
+pub struct Evm;
+
+impl ChainProfile for Evm {
+    fn workload(&self, seed: &ChainSeed, _job: &TodoJob) -> Vec<JsonRpcMethod> {
+        let mut eth_methods = vec![
+            JsonRpcMethod {
+                payload: 
+                    r#"{
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "method": "eth_sendRawTransaction",
+                        "params": ["0xf86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"]
+                    }"#.into(),
+                weight: 16
+            }
+        ];
+        push_seeded_methods(
+            &mut eth_methods,
+            &seed.addresses,
+            r#"{
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "eth_getCode",
+                "params": ["0x5B56438000bAc5ed2c6E0c1EcFF4354aBfFaf889","latest"]
+            }"#,
+            88,
+            |address| format!(r#"{{"jsonrpc": "2.0", "id": 1, "method": "eth_getCode", "params": ["{}", "latest"]}}"#, address)
+        );
+        eth_methods.push(
+            JsonRpcMethod {
+                payload:
+                    r#"{
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "method": "eth_getLogs",
+                        "params": [{"address": "0xdAC17F958D2ee523a2206206994597C13D831ec7"}]
+                    }"#.into(),
+                weight: 252
+            }
+        );
+        push_seeded_methods(
+            &mut eth_methods,
+            &seed.tx_hashes,
+            r#"{
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "eth_getTransactionByHash",
+                "params": ["0x04b713fdbbf14d4712df5ccc7bb3dfb102ac28b99872506a363c0dcc0ce4343c"]
+            }"#,
+            255,
+            |hash| format!(r#"{{"jsonrpc": "2.0", "id": 1, "method": "eth_getTransactionByHash", "params": ["{}"]}}"#, hash)
+        );
+        eth_methods.push(
+            JsonRpcMethod {
+                payload:
+                    r#"{
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "method": "eth_blockNumber",
+                        "params": []
+                    }"#.into(),
+                weight: 333
+            }
+        );
+        push_seeded_methods(
+            &mut eth_methods,
+            &seed.addresses,
+            r#"{
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "eth_getTransactionCount",
+                "params": ["0x8D97689C9818892B700e27F316cc3E41e17fBeb9", "latest"]
+            }"#,
+            390,
+            |address| format!(r#"{{"jsonrpc": "2.0", "id": 1, "method": "eth_getTransactionCount", "params": ["{}", "latest"]}}"#, address)
+        );
+        push_seeded_methods(
+            &mut eth_methods,
+            &seed.block_numbers,
+            r#"{
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "eth_getBlockByNumber",
+                "params": ["0xc5043f",false]
+            }"#,
+            399,
+            |number| format!(r#"{{"jsonrpc": "2.0", "id": 1, "method": "eth_getBlockByNumber", "params": ["{:#x}", false]}}"#, number)
+        );
+        push_seeded_methods(
+            &mut eth_methods,
+            &seed.addresses,
+            r#"{
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "eth_getBalance",
+                "params": ["0x8D97689C9818892B700e27F316cc3E41e17fBeb9", "latest"]
+            }"#,
+            545,
+            |address| format!(r#"{{"jsonrpc": "2.0", "id": 1, "method": "eth_getBalance", "params": ["{}", "latest"]}}"#, address)
+        );
+        push_seeded_methods(
+            &mut eth_methods,
+            &seed.tx_hashes,
+            r#"{
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "eth_getTransactionReceipt",
+                "params": ["0x04b713fdbbf14d4712df5ccc7bb3dfb102ac28b99872506a363c0dcc0ce4343c"]
+            }"#,
+            607,
+            |hash| format!(r#"{{"jsonrpc": "2.0", "id": 1, "method": "eth_getTransactionReceipt", "params": ["{}"]}}"#, hash)
+        );
+        eth_methods.push(
+            JsonRpcMethod {
+                payload:
+                    r#"{
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "method": "eth_call",
+                        "params": [{"from":null,"to":"0x6b175474e89094c44da98b954eedeac495271d0f","data":"0x70a082310000000000000000000000006E0d01A76C3Cf4288372a29124A26D4353EE51BE"}, "latest"]
+                    }"#.into(),
+                weight: 1928
+            }
+        );
+        eth_methods
+    }
+
+    fn seed<'a>(&'a self, client: &'a reqwest::Client, job: &'a TodoJob) -> BoxFuture<'a, Option<ChainSeed>> {
+        Box::pin(seed_from_node(client, job))
+    }
+
+    fn health_probe(&self) -> &'static str {
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "eth_chainId", "params": []}"#
+    }
+
+    fn validates_response_body(&self) -> bool {
+        // Rate limits and node failures come as JSON-RPC errors inside HTTP 200 responses
+        true
+    }
+
+    // e.g. -32005 => limit exceeded, -32603 => internal error are fails
+    fn response_is_ok(&self, body: &str) -> bool {
+        json_rpc_response_is_ok(body, |code| EVM_EXPECTED_ERROR_CODES.contains(&code))
+    }
+}
+
+// EVM JSON-RPC error codes that are a valid answer from a healthy node:
+// 3 => execution reverted
+// -32000 => transaction rejected by the node (e.g. the workload's eth_sendRawTransaction: nonce too low, already known)
+// -32003 => transaction rejected (Besu, Nethermind)
+const EVM_EXPECTED_ERROR_CODES: [i64; 3] = [3, -32000, -32003];
+
+async fn seed_from_node(
+    client: &reqwest::Client,
+    job: &TodoJob
+) -> Option<ChainSeed> {
+    let mut seed = ChainSeed::default();
+    let latest = rpc_call(client, job, "eth_blockNumber", json!([])).await?;
+    let latest = u64::from_str_radix(latest.as_str()?.trim_start_matches("0x"), 16).ok()?;
+
+    for block_number in (latest.saturating_sub(SEED_BLOCKS - 1)..=latest).rev() {
+        let block = match rpc_call(client, job, "eth_getBlockByNumber", json!([format!("{:#x}", block_number), true])).await {
+            Some(b) => b,
+            None => continue
+        };
+        seed.block_numbers.push(block_number);
+        if let Some(hash) = block["hash"].as_str() {
+            seed.block_hashes.push(hash.to_string());
+        }
+        for tx in block["transactions"].as_array().into_iter().flatten() {
+            if let Some(hash) = tx["hash"].as_str() {
+                push_unique(&mut seed.tx_hashes, hash);
+            }
+            for field in ["from", "to"] {
+                if let Some(address) = tx[field].as_str() {
+                    push_unique(&mut seed.addresses, address);
+                }
+            }
+        }
+    }
+    if seed.block_numbers.is_empty() {
+        return None;
+    }
+    Some(seed)
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::Evm;
+    use crate::chains::ChainProfile;
+    use crate::models::{ChainSeed, RpcPayload, TodoJob};
+
+
+    #[test]
+    fn test_unseeded_eth_methods_are_valid_json() {
+        let job: TodoJob = serde_json::from_str(r#"{"chain": "EVM", "endpoint_url": "http://localhost", "num_threads": 1, "duration": 1}"#).unwrap();
+        for method in Evm.workload(&ChainSeed::default(), &job) {
+            match method.payload {
+                RpcPayload::Post(_) => assert!(serde_json::from_str::<serde_json::Value>(method.payload.body().unwrap()).is_ok()),
+                get => panic!("Unexpected GET payload {:?}", get)
+            }
+        }
+    }
+}
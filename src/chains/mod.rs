@@ -0,0 +1,210 @@
+pub mod btc;
+pub mod cosmos;
+pub mod evm;
+pub mod sol;
+pub mod substrate;
+
+use std::fmt;
+use std::str::FromStr;
+
+use futures::future::BoxFuture;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::models::{ChainSeed, JsonRpcMethod, RpcPayload, TodoJob};
+
+// How many of the most recent blocks are scanned for tx hashes and addresses
+pub(crate) const SEED_BLOCKS: u64 = 3;
+// Upper bound for each kind of seeded value, keeps the generated payload mix small
+pub(crate) const MAX_SEED_VALUES: usize = 20;
+
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chain {
+    #[serde(rename = "EVM")]
+    Evm,
+    #[serde(rename = "BTC")]
+    Btc,
+    #[serde(rename = "SOL")]
+    Sol,
+    #[serde(rename = "COSMOS")]
+    Cosmos,
+    #[serde(rename = "SUBSTRATE")]
+    Substrate
+}
+
+impl Chain {
+    pub const ALL: [Chain; 5] = [Chain::Evm, Chain::Btc, Chain::Sol, Chain::Cosmos, Chain::Substrate];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Chain::Evm => "EVM",
+            Chain::Btc => "BTC",
+            Chain::Sol => "SOL",
+            Chain::Cosmos => "COSMOS",
+            Chain::Substrate => "SUBSTRATE"
+        }
+    }
+
+    // Everything chain specific the worker needs is behind this profile
+    pub fn profile(&self) -> &'static dyn ChainProfile {
+        match self {
+            Chain::Evm => &evm::Evm,
+            Chain::Btc => &btc::Btc,
+            Chain::Sol => &sol::Sol,
+            Chain::Cosmos => &cosmos::Cosmos,
+            Chain::Substrate => &substrate::Substrate
+        }
+    }
+}
+
+impl FromStr for Chain {
+    type Err = String;
+
+    fn from_str(chain: &str) -> Result<Chain, String> {
+        match Chain::ALL.iter().find(|c| c.as_str() == chain) {
+            Some(c) => Ok(*c),
+            None => Err(format!("Chain field provided: {}", chain))
+        }
+    }
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+
+pub trait ChainProfile: Send + Sync {
+    // Weighted mix of requests the job's threads send to the endpoint
+    fn workload(&self, seed: &ChainSeed, job: &TodoJob) -> Vec<JsonRpcMethod>;
+
+    // Pre-flight: real recent blocks, txs and addresses queried from the target node.
+    // None if the node could not be queried, the workload then uses its default values.
+    fn seed<'a>(&'a self, client: &'a reqwest::Client, job: &'a TodoJob) -> BoxFuture<'a, Option<ChainSeed>>;
+
+    // Cheap JSON-RPC request POSTed before the run to check the endpoint is up
+    fn health_probe(&self) -> &'static str;
+
+    // Chains reporting errors inside HTTP 200 responses need their bodies checked
+    fn validates_response_body(&self) -> bool {
+        false
+    }
+
+    // Checks the bodies when validates_response_body, and every element of a JSON-RPC batch response
+    fn response_is_ok(&self, body: &str) -> bool {
+        json_rpc_response_is_ok(body, |_| false)
+    }
+}
+
+
+// Spreads a method's weight over the payloads built from each seeded value,
+// so the overall mix stays the same. Without seeded values the default payload is used.
+pub(crate) fn push_seeded_methods<T, P: Into<RpcPayload>>(
+    methods: &mut Vec<JsonRpcMethod>,
+    seeded_values: &[T],
+    default_payload: impl Into<RpcPayload>,
+    weight: u32,
+    payload: impl Fn(&T) -> P
+) {
+    if seeded_values.is_empty() {
+        methods.push(
+            JsonRpcMethod {
+                payload: default_payload.into(),
+                weight
+            }
+        );
+        return;
+    }
+    let seeded_weight = std::cmp::max(weight / seeded_values.len() as u32, 1);
+    for value in seeded_values {
+        methods.push(
+            JsonRpcMethod {
+                payload: payload(value).into(),
+                weight: seeded_weight
+            }
+        );
+    }
+}
+
+// Sends a single JSON-RPC request and returns its "result" field
+pub(crate) async fn rpc_call(
+    client: &reqwest::Client,
+    job: &TodoJob,
+    method: &str,
+    params: Value
+) -> Option<Value> {
+    let request = job.authenticate(client.post(job.http_url())
+                            .json(&json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params})));
+    let response: Value = request.send().await.ok()?.json().await.ok()?;
+    match response.get("result") {
+        Some(Value::Null) | None => None,
+        Some(result) => Some(result.clone())
+    }
+}
+
+// A JSON-RPC response is ok with a result, or with an error that healthy nodes of the chain
+// answer as well (e.g. a rejected transaction). Rate limits and node failures are errors
+pub(crate) fn json_rpc_response_is_ok(body: &str, is_expected_error: impl Fn(i64) -> bool) -> bool {
+    match serde_json::from_str::<Value>(body) {
+        Ok(response) => json_rpc_value_is_ok(&response, is_expected_error),
+        Err(_) => false
+    }
+}
+
+pub(crate) fn json_rpc_value_is_ok(response: &Value, is_expected_error: impl Fn(i64) -> bool) -> bool {
+    match response.get("error") {
+        // Bitcoin Core answers "error": null next to the result
+        None | Some(Value::Null) => response.get("result").is_some(),
+        Some(error) => error["code"].as_i64().is_some_and(is_expected_error)
+    }
+}
+
+pub(crate) fn push_unique(values: &mut Vec<String>, value: &str) {
+    if values.len() < MAX_SEED_VALUES && !values.iter().any(|v| v == value) {
+        values.push(value.to_string());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::{push_seeded_methods, Chain};
+    use crate::models::RpcPayload;
+    use std::str::FromStr;
+
+
+    #[test]
+    fn test_seeded_methods_keep_total_weight() {
+        let mut methods = Vec::new();
+        let tx_hashes = vec!["0xaa".to_string(), "0xbb".to_string()];
+        push_seeded_methods(&mut methods, &tx_hashes, "default", 600, |hash| hash.clone());
+
+        assert_eq!(methods.len(), 2);
+        assert_eq!(methods.iter().map(|m| m.weight).sum::<u32>(), 600);
+        assert_eq!(methods[1].payload, RpcPayload::from("0xbb"));
+    }
+
+    #[test]
+    fn test_json_rpc_errors_are_fails() {
+        let rate_limited = r#"{"jsonrpc":"2.0","error":{"code":-32005,"message":"limit exceeded"},"id":1}"#;
+        for chain in [Chain::Evm, Chain::Btc, Chain::Cosmos] {
+            assert!(chain.profile().validates_response_body());
+            assert!(!chain.profile().response_is_ok(rate_limited));
+        }
+        assert!(Chain::Evm.profile().response_is_ok(r#"{"jsonrpc":"2.0","error":{"code":-32000,"message":"nonce too low"},"id":1}"#));
+        assert!(Chain::Btc.profile().response_is_ok(r#"{"result":null,"error":null,"id":"1"}"#));
+        assert!(Chain::Btc.profile().response_is_ok(r#"{"result":null,"error":{"code":-27,"message":"Transaction already in block chain"},"id":"1"}"#));
+        assert!(!Chain::Cosmos.profile().response_is_ok(r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32603,"message":"Internal error"}}"#));
+        assert!(Chain::Cosmos.profile().response_is_ok(r#"{"block_id":{"hash":"AB"},"block":{}}"#));
+    }
+
+    #[test]
+    fn test_chain_from_str() {
+        assert_eq!(Chain::from_str("COSMOS"), Ok(Chain::Cosmos));
+        assert!(Chain::from_str("evm").is_err());
+    }
+}
@@ -0,0 +1,183 @@
+use futures::future::BoxFuture;
+use reqwest;
+use serde_json::json;
+
+use crate::chains::{ChainProfile, json_rpc_response_is_ok, push_seeded_methods, rpc_call, push_unique, SEED_BLOCKS};
+use crate::models::{ChainSeed, JsonRpcMethod, TodoJob};
+
+
+// Solana JSON-RPC nodes
+pub struct Sol;
+
+impl ChainProfile for Sol {
+    fn workload(&self, seed: &ChainSeed, _job: &TodoJob) -> Vec<JsonRpcMethod> {
+        let mut sol_methods = vec![
+            JsonRpcMethod {
+                payload:
+                    r#"{
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "method": "sendTransaction",
+                        "params": ["4hXTCkRzt9WyecNzV1XPgCDfGAZzQKNxLXgynz5QDuWWPSAZBZSHptvWRL3BjCvzUXRdKvHL2b7yGrRQcWyaqsaBCncVG7BFggS8w9snUts67BSh3EqKpXLUm5UMHfD7ZBe9GhARjbNQMLJ1QD3Spr6oMTBU6EhdB4RD8CP2xUxr2u3d6fos36PD98XS6oX8TQjLpsMwncs5DAMiD4nNnR8NBfyghGCWvCVifVwvA8B8TJxE1aiyiv2L429BCWfyzAme5sZW8rDb14NeCQHhZbtNqfXhcp2tAnaAT"]
+                    }"#.into(),
+                weight: 16
+            },
+            JsonRpcMethod {
+                payload:
+                    r#"{
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "method": "getProgramAccounts",
+                        "params": ["Vote111111111111111111111111111111111111111", {"encoding": "base64", "dataSlice": {"offset": 0, "length": 0}}]
+                    }"#.into(),
+                weight: 40
+            }
+        ];
+        push_seeded_methods(
+            &mut sol_methods,
+            &seed.addresses,
+            r#"{
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "getSignaturesForAddress",
+                "params": ["Vote111111111111111111111111111111111111111", {"limit": 10}]
+            }"#,
+            252,
+            |address| format!(r#"{{"jsonrpc": "2.0", "id": 1, "method": "getSignaturesForAddress", "params": ["{}", {{"limit": 10}}]}}"#, address)
+        );
+        push_seeded_methods(
+            &mut sol_methods,
+            &seed.tx_hashes,
+            r#"{
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "getTransaction",
+                "params": ["2nBhEBYYvfaAe16UMNqRHre4YNSskvuYgx3M6E4JP1oDYvZEJHvoPzyUidNgNX5r9sTyN1J9UxtbCXy2rqYcuyuv", {"encoding": "json", "maxSupportedTransactionVersion": 0}]
+            }"#,
+            399,
+            |signature| format!(r#"{{"jsonrpc": "2.0", "id": 1, "method": "getTransaction", "params": ["{}", {{"encoding": "json", "maxSupportedTransactionVersion": 0}}]}}"#, signature)
+        );
+        sol_methods.push(
+            JsonRpcMethod {
+                payload:
+                    r#"{
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "method": "getLatestBlockhash",
+                        "params": [{"commitment": "finalized"}]
+                    }"#.into(),
+                weight: 545
+            }
+        );
+        push_seeded_methods(
+            &mut sol_methods,
+            &seed.addresses,
+            r#"{
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "getBalance",
+                "params": ["83astBRguLMdt2h5U1Tpdq5tjFoJ6noeGwaY3mDLVcri"]
+            }"#,
+            607,
+            |address| format!(r#"{{"jsonrpc": "2.0", "id": 1, "method": "getBalance", "params": ["{}"]}}"#, address)
+        );
+        push_seeded_methods(
+            &mut sol_methods,
+            &seed.addresses,
+            r#"{
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "getAccountInfo",
+                "params": ["vines1vzrYbzLMRdu58ou5XTby4qAqVRLmqo36NKPTg", {"encoding": "base64"}]
+            }"#,
+            1928,
+            |address| format!(r#"{{"jsonrpc": "2.0", "id": 1, "method": "getAccountInfo", "params": ["{}", {{"encoding": "base64"}}]}}"#, address)
+        );
+        sol_methods
+    }
+
+    fn seed<'a>(&'a self, client: &'a reqwest::Client, job: &'a TodoJob) -> BoxFuture<'a, Option<ChainSeed>> {
+        Box::pin(seed_from_node(client, job))
+    }
+
+    fn health_probe(&self) -> &'static str {
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "getHealth"}"#
+    }
+
+    fn validates_response_body(&self) -> bool {
+        // Solana nodes report most failures as JSON-RPC errors inside HTTP 200 responses
+        true
+    }
+
+    // e.g. -32005 => node is unhealthy/behind, -32603 => internal error, rate limits are fails
+    fn response_is_ok(&self, body: &str) -> bool {
+        json_rpc_response_is_ok(body, |code| SOLANA_EXPECTED_ERROR_CODES.contains(&code))
+    }
+}
+
+// Solana JSON-RPC error codes that are a valid answer from a healthy node:
+// -32002 => sendTransaction preflight simulation failed
+// -32003 => transaction signature verification failed
+// -32004 => block not available for slot
+// -32007 => slot was skipped or is missing due to a ledger jump
+// -32009 => slot was skipped or is missing in long-term storage
+const SOLANA_EXPECTED_ERROR_CODES: [i64; 5] = [-32002, -32003, -32004, -32007, -32009];
+
+async fn seed_from_node(
+    client: &reqwest::Client,
+    job: &TodoJob
+) -> Option<ChainSeed> {
+    let mut seed = ChainSeed::default();
+    let latest = rpc_call(client, job, "getSlot", json!([{"commitment": "finalized"}])).await?.as_u64()?;
+
+    // Slots can be skipped by their leader, so walk back until enough blocks are found
+    for slot in (latest.saturating_sub(SEED_BLOCKS * 4)..=latest).rev() {
+        if seed.block_numbers.len() as u64 >= SEED_BLOCKS {
+            break;
+        }
+        let block = match rpc_call(client, job, "getBlock", json!([slot, {
+            "encoding": "json",
+            "maxSupportedTransactionVersion": 0,
+            "transactionDetails": "accounts",
+            "rewards": false
+        }])).await {
+            Some(b) => b,
+            None => continue
+        };
+        seed.block_numbers.push(slot);
+        if let Some(hash) = block["blockhash"].as_str() {
+            seed.block_hashes.push(hash.to_string());
+        }
+        for tx in block["transactions"].as_array().into_iter().flatten() {
+            if let Some(signature) = tx["transaction"]["signatures"][0].as_str() {
+                push_unique(&mut seed.tx_hashes, signature);
+            }
+            for account in tx["transaction"]["accountKeys"].as_array().into_iter().flatten() {
+                if let Some(pubkey) = account["pubkey"].as_str() {
+                    push_unique(&mut seed.addresses, pubkey);
+                }
+            }
+        }
+    }
+    if seed.block_numbers.is_empty() {
+        return None;
+    }
+    Some(seed)
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::Sol;
+    use crate::chains::ChainProfile;
+
+
+    #[test]
+    fn test_solana_expected_errors_are_not_fails() {
+        assert!(Sol.response_is_ok(r#"{"jsonrpc":"2.0","result":{"value":0},"id":1}"#));
+        assert!(Sol.response_is_ok(r#"{"jsonrpc":"2.0","error":{"code":-32002,"message":"Transaction simulation failed"},"id":1}"#));
+        assert!(!Sol.response_is_ok(r#"{"jsonrpc":"2.0","error":{"code":-32005,"message":"Node is unhealthy"},"id":1}"#));
+        assert!(!Sol.response_is_ok("Too many requests"));
+    }
+}
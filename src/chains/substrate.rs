@@ -0,0 +1,154 @@
+use futures::future::BoxFuture;
+use reqwest;
+use serde_json::json;
+
+use crate::chains::{ChainProfile, json_rpc_response_is_ok, push_seeded_methods, rpc_call, SEED_BLOCKS};
+use crate::models::{ChainSeed, JsonRpcMethod, TodoJob};
+
+// Storage keys present on every FRAME based chain: System.Number and Timestamp.Now
+const SYSTEM_NUMBER_KEY: &str = "0x26aa394eea5630e07c48ae0c9558cef702a5c1b19ab7a04f536c519aca4983ac";
+const TIMESTAMP_NOW_KEY: &str = "0xf0c365c3cf59d671eb72da0e7a4113c49f1f0515f462cdcf84e0f1d6045dfcbb";
+
+// Substrate JSON-RPC error codes that are a valid answer from a healthy node:
+// 1001..=1016 => author_submitExtrinsic rejected the extrinsic (bad format, verification
+// failed, invalid transaction, pool limits), expected since the benchmark's extrinsic is not signed
+const SUBSTRATE_EXPECTED_ERROR_CODES: std::ops::RangeInclusive<i64> = 1001..=1016;
+
+
+// Substrate/Polkadot SDK based chains (Polkadot, Kusama, parachains)
+pub struct Substrate;
+
+impl ChainProfile for Substrate {
+    fn workload(&self, seed: &ChainSeed, _job: &TodoJob) -> Vec<JsonRpcMethod> {
+        let mut substrate_methods = vec![
+            JsonRpcMethod {
+                payload:
+                    r#"{
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "method": "author_submitExtrinsic",
+                        "params": ["0x280403000b5018ae4d8501"]
+                    }"#.into(),
+                weight: 16
+            },
+            JsonRpcMethod {
+                payload:
+                    r#"{
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "method": "system_health",
+                        "params": []
+                    }"#.into(),
+                weight: 88
+            },
+            JsonRpcMethod {
+                payload:
+                    r#"{
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "method": "state_getRuntimeVersion",
+                        "params": []
+                    }"#.into(),
+                weight: 252
+            }
+        ];
+        push_seeded_methods(
+            &mut substrate_methods,
+            &seed.block_hashes,
+            r#"{
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "chain_getBlock",
+                "params": []
+            }"#,
+            333,
+            |hash| format!(r#"{{"jsonrpc": "2.0", "id": 1, "method": "chain_getBlock", "params": ["{}"]}}"#, hash)
+        );
+        push_seeded_methods(
+            &mut substrate_methods,
+            &seed.block_numbers,
+            r#"{
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "chain_getBlockHash",
+                "params": [0]
+            }"#,
+            399,
+            |number| format!(r#"{{"jsonrpc": "2.0", "id": 1, "method": "chain_getBlockHash", "params": [{}]}}"#, number)
+        );
+        push_seeded_methods(
+            &mut substrate_methods,
+            &seed.block_hashes,
+            format!(r#"{{"jsonrpc": "2.0", "id": 1, "method": "state_getStorage", "params": ["{}"]}}"#, TIMESTAMP_NOW_KEY),
+            607,
+            |hash| format!(r#"{{"jsonrpc": "2.0", "id": 1, "method": "state_getStorage", "params": ["{}", "{}"]}}"#, TIMESTAMP_NOW_KEY, hash)
+        );
+        push_seeded_methods(
+            &mut substrate_methods,
+            &seed.block_hashes,
+            format!(r#"{{"jsonrpc": "2.0", "id": 1, "method": "state_getStorage", "params": ["{}"]}}"#, SYSTEM_NUMBER_KEY),
+            1928,
+            |hash| format!(r#"{{"jsonrpc": "2.0", "id": 1, "method": "state_getStorage", "params": ["{}", "{}"]}}"#, SYSTEM_NUMBER_KEY, hash)
+        );
+        substrate_methods
+    }
+
+    fn seed<'a>(&'a self, client: &'a reqwest::Client, job: &'a TodoJob) -> BoxFuture<'a, Option<ChainSeed>> {
+        Box::pin(seed_from_node(client, job))
+    }
+
+    fn health_probe(&self) -> &'static str {
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "system_health", "params": []}"#
+    }
+
+    fn validates_response_body(&self) -> bool {
+        // Substrate nodes return JSON-RPC error objects inside HTTP 200 responses
+        true
+    }
+
+    // e.g. -32601 => method not found/unsafe RPC disabled, -32603 => internal error are fails
+    fn response_is_ok(&self, body: &str) -> bool {
+        json_rpc_response_is_ok(body, |code| SUBSTRATE_EXPECTED_ERROR_CODES.contains(&code))
+    }
+}
+
+async fn seed_from_node(
+    client: &reqwest::Client,
+    job: &TodoJob
+) -> Option<ChainSeed> {
+    let mut seed = ChainSeed::default();
+    let header = rpc_call(client, job, "chain_getHeader", json!([])).await?;
+    // Substrate encodes block numbers as hex strings
+    let latest = u64::from_str_radix(header["number"].as_str()?.trim_start_matches("0x"), 16).ok()?;
+
+    for block_number in (latest.saturating_sub(SEED_BLOCKS - 1)..=latest).rev() {
+        let hash = match rpc_call(client, job, "chain_getBlockHash", json!([block_number])).await {
+            Some(h) => h,
+            None => continue
+        };
+        if let Some(hash) = hash.as_str() {
+            seed.block_numbers.push(block_number);
+            seed.block_hashes.push(hash.to_string());
+        }
+    }
+    if seed.block_numbers.is_empty() {
+        return None;
+    }
+    Some(seed)
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::Substrate;
+    use crate::chains::ChainProfile;
+
+
+    #[test]
+    fn test_substrate_error_objects() {
+        assert!(Substrate.response_is_ok(r#"{"jsonrpc":"2.0","result":"0x1234","id":1}"#));
+        assert!(Substrate.response_is_ok(r#"{"jsonrpc":"2.0","error":{"code":1010,"message":"Invalid Transaction"},"id":1}"#));
+        assert!(!Substrate.response_is_ok(r#"{"jsonrpc":"2.0","error":{"code":-32601,"message":"Method not found"},"id":1}"#));
+    }
+}
@@ -1,8 +1,8 @@
+use std::path::PathBuf;
+
 use config::ConfigError;
 use serde::Deserialize;
 use slog::{o, Drain, Logger};
-use slog_async;
-use slog_term;
 
 extern crate slog_json;
 
@@ -14,18 +14,154 @@ pub struct Config {
     pub server_port: u32,
 
     // Verbosity level of logging
+    #[allow(dead_code)]
     pub rust_log: String,
 
+    // "redis" (default) or "memory", which runs the API and the redis-workers without Redis
+    #[serde(default = "default_queue_backend")]
+    pub queue_backend: String,
+
     // IP Address and Port the Redis db service listens on 
+    #[serde(default = "default_redis_address")]
     pub redis_address: String,
+    #[serde(default = "default_redis_port")]
     pub redis_port: String,
+    // Redis ACL user and password, not set => no authentication
+    #[serde(default)]
+    pub redis_username: Option<String>,
+    #[serde(default)]
+    pub redis_password: Option<String>,
+    // Connects to Redis through TLS, as a rediss:// url would
+    #[serde(default)]
+    pub redis_tls: bool,
+    #[serde(default)]
+    pub redis_db: i64,
+    // Prepended to every Redis key of the service (RSMQ's included), so deployments can share a Redis
+    #[serde(default)]
+    pub redis_key_prefix: String,
+    // RSMQ queue the jobs are sent through
+    #[serde(default = "default_redis_queue_name")]
+    pub redis_queue_name: String,
 
     // Number of "redis-workers" that handle new jobs
     pub num_of_workers: u32,
     
     // Maximum accepted percentage of failed requests in a job for it to be considered successful
     // e.g. 5% of responses are fails, the job fails
-    pub fail_percentage_treshold: f64
+    pub fail_percentage_treshold: f64,
+
+    // Bounds of every job's num_threads and duration (and warmup), in seconds
+    #[serde(default = "default_max_threads")]
+    pub max_threads: u32,
+    #[serde(default = "default_max_duration")]
+    pub max_duration: u32,
+    // Bound of every job's compare_endpoints
+    #[serde(default = "default_max_compare_endpoints")]
+    pub max_compare_endpoints: u32,
+    // Bound of every job's batch_size, the calls of a batch are held in memory
+    #[serde(default = "default_max_batch_size")]
+    pub max_batch_size: u32,
+    // Bounds of every WS job's connections per thread and requests in flight per connection
+    #[serde(default = "default_max_ws_connections")]
+    pub max_ws_connections: u32,
+    #[serde(default = "default_max_ws_pipeline_depth")]
+    pub max_ws_pipeline_depth: u32,
+
+    // Default timeouts of the jobs' requests, a TodoJob can override them
+    #[serde(default = "default_connect_timeout_ms")]
+    pub connect_timeout_ms: u64,
+    #[serde(default = "default_request_timeout_ms")]
+    pub request_timeout_ms: u64,
+
+    // Base64 encoded 256 bit key the jobs' credentials are encrypted with in Redis
+    pub secrets_key: String,
+
+    // API keys of the clients allowed to use the /jobs routes, "<client_name>:<api_key>,..."
+    // more can be added at runtime to the "api_keys" Redis hash
+    #[serde(default)]
+    pub api_keys: String,
+
+    // Limits every client's jobs are held to, 0 => unlimited
+    #[serde(default)]
+    pub quota_max_concurrent_jobs: u32,
+    #[serde(default)]
+    pub quota_max_threads: u32,
+    #[serde(default)]
+    pub quota_max_duration: u32,
+    // Requests a client's jobs may send per day (UTC)
+    #[serde(default)]
+    pub quota_daily_requests: u64,
+
+    // Comma separated schemes, host names (or *.domain) and CIDR ranges jobs may target,
+    // an empty allowlist allows every host that isn't denied
+    #[serde(default = "default_allowed_schemes")]
+    pub allowed_schemes: String,
+    #[serde(default)]
+    pub allowed_hosts: String,
+    #[serde(default = "default_denied_hosts")]
+    pub denied_hosts: String,
+
+    // Directory the jobs' client_cert_path and client_key_path are read from,
+    // empty => jobs can't use mTLS client certificates
+    #[serde(default)]
+    pub client_cert_dir: String
+}
+
+fn default_queue_backend() -> String {
+    String::from("redis")
+}
+
+fn default_redis_address() -> String {
+    String::from("127.0.0.1")
+}
+
+fn default_redis_port() -> String {
+    String::from("6379")
+}
+
+fn default_redis_queue_name() -> String {
+    String::from("jobs_q")
+}
+
+fn default_max_threads() -> u32 {
+    1000
+}
+
+fn default_max_duration() -> u32 {
+    3600
+}
+
+fn default_max_compare_endpoints() -> u32 {
+    4
+}
+
+fn default_max_batch_size() -> u32 {
+    1000
+}
+
+fn default_max_ws_connections() -> u32 {
+    16
+}
+
+fn default_max_ws_pipeline_depth() -> u32 {
+    64
+}
+
+fn default_connect_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_request_timeout_ms() -> u64 {
+    30000
+}
+
+fn default_allowed_schemes() -> String {
+    String::from("http,https,ws,wss")
+}
+
+// Loopback, private, link-local (cloud metadata) and unspecified addresses
+fn default_denied_hosts() -> String {
+    String::from("localhost,0.0.0.0/8,10.0.0.0/8,100.64.0.0/10,127.0.0.0/8,169.254.0.0/16,172.16.0.0/12,192.168.0.0/16,::/128,::1/128,fc00::/7,fe80::/10")
 }
 
 
@@ -37,6 +173,16 @@ impl Config {
         config.try_into()
     }
 
+    // Canonical CLIENT_CERT_DIR, the jobs' certificate paths are checked against it
+    pub fn client_cert_dir(&self) -> Result<Option<PathBuf>, String> {
+        if self.client_cert_dir.is_empty() {
+            return Ok(None);
+        }
+        let client_cert_dir = PathBuf::from(&self.client_cert_dir).canonicalize()
+            .map_err(|e| format!("Invalid CLIENT_CERT_DIR {}: {}", self.client_cert_dir, e))?;
+        Ok(Some(client_cert_dir))
+    }
+
     pub fn configure_log(&self) -> Logger {
         // create Logger
         let decorator = slog_term::TermDecorator::new().build();
@@ -0,0 +1,215 @@
+use std::net::{IpAddr, SocketAddr};
+
+use crate::config::Config;
+
+
+// Where jobs may send load to: the endpoints' schemes, hosts and the addresses they resolve to.
+// An address is allowed when it matches an allowed CIDR, otherwise it mustn't match a denied one
+#[derive(Clone, Debug)]
+pub struct EndpointPolicy {
+    schemes: Vec<String>,
+    allowed: Vec<HostRule>,
+    denied: Vec<HostRule>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum HostRule {
+    // Exact host name, or any subdomain with a leading "*."
+    Domain(String),
+    Cidr(IpAddr, u8)
+}
+
+impl EndpointPolicy {
+    pub fn from_config(config: &Config) -> Result<EndpointPolicy, String> {
+        Ok(EndpointPolicy {
+            schemes: split_list(config.allowed_schemes.as_str()).map(|s| s.to_lowercase()).collect(),
+            allowed: split_list(config.allowed_hosts.as_str()).map(HostRule::parse).collect::<Result<_, _>>()?,
+            denied: split_list(config.denied_hosts.as_str()).map(HostRule::parse).collect::<Result<_, _>>()?
+        })
+    }
+
+    // Checks the url's scheme and host, without resolving it
+    pub fn check_url(&self, url: &str) -> Result<(), String> {
+        let url = reqwest::Url::parse(url).map_err(|e| format!("Invalid url {}: {}", url, e))?;
+        if !self.schemes.iter().any(|scheme| scheme == url.scheme()) {
+            return Err(format!("Scheme {} is not allowed", url.scheme()));
+        }
+        match url.host() {
+            Some(url::Host::Ipv4(ip)) => self.check_ip(IpAddr::V4(ip), false),
+            Some(url::Host::Ipv6(ip)) => self.check_ip(IpAddr::V6(ip), false),
+            Some(url::Host::Domain(host)) => self.check_domain(host).map(|_| ()),
+            None => Err(format!("Url {} has no host", url))
+        }
+    }
+
+    // check_url, then every address the url's host resolves to
+    pub async fn check_resolved(&self, url: &str) -> Result<(), String> {
+        self.check_url(url)?;
+        let parsed = reqwest::Url::parse(url).map_err(|e| e.to_string())?;
+        if let Some(url::Host::Domain(host)) = parsed.host() {
+            let host_allowed = self.check_domain(host)?;
+            let addrs = tokio::net::lookup_host((host, parsed.port_or_known_default().unwrap_or(0))).await
+                .map_err(|e| format!("Failed to resolve {}: {}", host, e))?;
+            for addr in addrs {
+                self.check_ip(addr.ip(), host_allowed)?;
+            }
+        }
+        Ok(())
+    }
+
+    // check_url, then the allowed addresses among the ones the url's host resolves to, to connect
+    // to directly: resolving again when connecting could return other (denied) addresses
+    pub async fn resolve_allowed(&self, url: &str) -> Result<Vec<SocketAddr>, String> {
+        self.check_url(url)?;
+        let parsed = reqwest::Url::parse(url).map_err(|e| e.to_string())?;
+        let port = parsed.port_or_known_default().unwrap_or(0);
+        let addrs = match parsed.host() {
+            Some(url::Host::Ipv4(ip)) => vec![SocketAddr::new(IpAddr::V4(ip), port)],
+            Some(url::Host::Ipv6(ip)) => vec![SocketAddr::new(IpAddr::V6(ip), port)],
+            Some(url::Host::Domain(host)) => {
+                let addrs = tokio::net::lookup_host((host, port)).await
+                    .map_err(|e| format!("Failed to resolve {}: {}", host, e))?;
+                self.filter_addrs(host, addrs.collect())
+            },
+            None => Vec::new()
+        };
+        if addrs.is_empty() {
+            return Err(format!("{} doesn't resolve to any allowed address", url));
+        }
+        Ok(addrs)
+    }
+
+    // The allowed addresses among the ones a job's host resolved to
+    pub fn filter_addrs(&self, host: &str, addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        let host_allowed = match self.check_domain(host) {
+            Ok(host_allowed) => host_allowed,
+            Err(_) => return Vec::new()
+        };
+        addrs.into_iter().filter(|addr| self.check_ip(addr.ip(), host_allowed).is_ok()).collect()
+    }
+
+    // Returns whether the host is in the allowlist, hosts that aren't may
+    // still resolve to an allowed CIDR
+    fn check_domain(&self, host: &str) -> Result<bool, String> {
+        let host = host.to_lowercase();
+        if self.denied.iter().any(|rule| rule.matches_domain(host.as_str())) {
+            return Err(format!("Host {} is not allowed", host));
+        }
+        let host_allowed = self.allowed.iter().any(|rule| rule.matches_domain(host.as_str()));
+        if !self.allowed.is_empty() && !host_allowed && !self.allowed.iter().any(|rule| matches!(rule, HostRule::Cidr(..))) {
+            return Err(format!("Host {} is not in the allowlist", host));
+        }
+        Ok(host_allowed)
+    }
+
+    fn check_ip(&self, ip: IpAddr, host_allowed: bool) -> Result<(), String> {
+        if self.allowed.iter().any(|rule| rule.matches_ip(ip)) {
+            return Ok(());
+        }
+        if self.denied.iter().any(|rule| rule.matches_ip(ip)) {
+            return Err(format!("Address {} is not allowed", ip));
+        }
+        if !self.allowed.is_empty() && !host_allowed {
+            return Err(format!("Address {} is not in the allowlist", ip));
+        }
+        Ok(())
+    }
+}
+
+impl HostRule {
+    fn parse(rule: &str) -> Result<HostRule, String> {
+        let rule = rule.to_lowercase();
+        let (ip, prefix) = match rule.split_once('/') {
+            Some((ip, prefix)) => (ip, Some(prefix)),
+            None => (rule.as_str(), None)
+        };
+        let ip: IpAddr = match ip.parse() {
+            Ok(ip) => ip,
+            Err(_) if prefix.is_none() => return Ok(HostRule::Domain(rule)),
+            Err(_) => return Err(format!("Invalid CIDR: {}", rule))
+        };
+        let max_prefix = if ip.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p.parse::<u8>().ok().filter(|p| *p <= max_prefix).ok_or(format!("Invalid CIDR: {}", rule))?,
+            None => max_prefix
+        };
+        Ok(HostRule::Cidr(ip, prefix))
+    }
+
+    fn matches_domain(&self, host: &str) -> bool {
+        match self {
+            HostRule::Domain(domain) => match domain.strip_prefix("*.") {
+                Some(suffix) => host.ends_with(format!(".{}", suffix).as_str()),
+                None => host == domain
+            },
+            HostRule::Cidr(..) => false
+        }
+    }
+
+    fn matches_ip(&self, ip: IpAddr) -> bool {
+        // IPv4-mapped IPv6 addresses are matched as IPv4
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            _ => ip
+        };
+        match (self, ip) {
+            (HostRule::Cidr(IpAddr::V4(network), prefix), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - *prefix as u32).unwrap_or(0);
+                u32::from(*network) & mask == u32::from(ip) & mask
+            },
+            (HostRule::Cidr(IpAddr::V6(network), prefix), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - *prefix as u32).unwrap_or(0);
+                u128::from(*network) & mask == u128::from(ip) & mask
+            },
+            _ => false
+        }
+    }
+}
+
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').map(|entry| entry.trim()).filter(|entry| !entry.is_empty())
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::{EndpointPolicy, HostRule};
+
+
+    fn policy(allowed: &str, denied: &str) -> EndpointPolicy {
+        EndpointPolicy {
+            schemes: vec![String::from("https"), String::from("wss")],
+            allowed: allowed.split(',').filter(|r| !r.is_empty()).map(|r| HostRule::parse(r).unwrap()).collect(),
+            denied: denied.split(',').filter(|r| !r.is_empty()).map(|r| HostRule::parse(r).unwrap()).collect()
+        }
+    }
+
+    #[test]
+    fn test_endpoint_policy() {
+        let default = policy("", "10.0.0.0/8,127.0.0.0/8,::1,localhost");
+        assert!(default.check_url("https://endpoints.omniatech.io/v1/eth/mainnet").is_ok());
+        assert!(default.check_url("http://endpoints.omniatech.io/v1/eth/mainnet").is_err());
+        assert!(default.check_url("https://10.5.0.6:6379").is_err());
+        assert!(default.check_url("wss://[::1]/ws").is_err());
+        assert!(default.check_url("https://[::ffff:127.0.0.1]/").is_err());
+        assert!(default.check_url("https://LOCALHOST/").is_err());
+
+        // Allowed CIDRs take precedence over the denied ones
+        let internal = policy("*.omniatech.io,10.5.0.9/32", "10.0.0.0/8");
+        assert!(internal.check_url("https://10.5.0.9/").is_ok());
+        assert!(internal.check_url("https://10.5.0.6/").is_err());
+        assert!(internal.check_url("https://endpoints.omniatech.io/").is_ok());
+        assert!(internal.check_url("https://1.1.1.1/").is_err());
+        assert!(HostRule::parse("10.0.0.0/33").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolved_addresses_are_allowed() {
+        let internal = policy("*.omniatech.io,10.5.0.9/32", "10.0.0.0/8,localhost");
+        assert_eq!(internal.resolve_allowed("wss://10.5.0.9:8546/ws").await.unwrap(), vec!["10.5.0.9:8546".parse().unwrap()]);
+        assert_eq!(internal.resolve_allowed("https://10.5.0.9/").await.unwrap(), vec!["10.5.0.9:443".parse().unwrap()]);
+        assert!(internal.resolve_allowed("wss://10.5.0.6/ws").await.is_err());
+        assert!(internal.resolve_allowed("wss://localhost/ws").await.is_err());
+    }
+}
@@ -1,18 +1,22 @@
+mod backend;
+mod chains;
 mod rest_api;
 mod config;
+mod endpoint_policy;
 mod models;
 mod redis_workers;
+mod secrets;
 
-use std::str::FromStr;
-
-use crate::rest_api::{health, handlers};
+use crate::rest_api::{auth, health, handlers, openapi, request_id};
+use crate::backend::Backend;
 use crate::config::Config;
-use crate::models::AppState;
+use crate::endpoint_policy::EndpointPolicy;
+use crate::models::{AppState, JobLimits, Quotas};
+use crate::redis_workers::config::WorkerConfig;
+use crate::secrets::SecretBox;
 
-use dotenv;
 use actix_web::{middleware ,App, HttpServer, web};
 use slog::{info};
-use rsmq_async::{Rsmq, RsmqConnection, RsmqOptions};
 
 
 #[actix_web::main]
@@ -22,53 +26,76 @@ async fn main() -> std::io::Result<()> {
 
     let config = Config::from_env().unwrap();
     let log = config.configure_log();
+    let secrets = SecretBox::from_key(config.secrets_key.as_str()).unwrap();
+    let api_keys = auth::parse_api_keys(config.api_keys.as_str());
+    let endpoint_policy = EndpointPolicy::from_config(&config).unwrap();
+    let client_cert_dir = config.client_cert_dir().unwrap();
+    let job_limits = JobLimits {
+        max_threads: config.max_threads,
+        max_duration: config.max_duration,
+        max_compare_endpoints: config.max_compare_endpoints,
+        max_batch_size: config.max_batch_size,
+        max_ws_connections: config.max_ws_connections,
+        max_ws_pipeline_depth: config.max_ws_pipeline_depth,
+        client_cert_dir: client_cert_dir.clone()
+    };
+    let quotas = Quotas {
+        max_concurrent_jobs: config.quota_max_concurrent_jobs,
+        max_threads: config.quota_max_threads,
+        max_duration: config.quota_max_duration,
+        daily_requests: config.quota_daily_requests
+    };
     info!(log, 
         "BENCHMARKING service started at http://{}:{}", 
         config.server_host, 
         config.server_port
     );
-    let options = RsmqOptions {
-        host: config.redis_address.clone(),
-        port: config.redis_port.clone(),
-        db: 0,
-        realtime: false,
-        password: None,
-        ns: String::from_str("rsmq").unwrap()
-    };
     
-    // Redis db & RSMQ setup
+    // Redis db & RSMQ setup (or their in-memory counterpart)
     // Redis is being used to syncronize the redis-workers, hence their name
-    // such that no two workers handle the same job
-    let connection = redis::Client::open(format!("redis://{}", config.redis_address))
-                                                .unwrap()
-                                                .get_async_connection()
-                                                .await
-                                                .unwrap();
+    // such that no two workers handle the same job.
     // RSMQ messages new jobs from the web server thread (actix thread) to the first available redis-workers  
-    let mut rsmq = Rsmq::new_with_connection(options.clone(), connection);
-
-    // Make sure the job queue is empty before creating it
-    rsmq.delete_queue("jobs_q").await;
-    rsmq.create_queue("jobs_q", None, None, None).await;
+    let backend = Backend::from_config(&config).await.unwrap();
     
     // Spawn .env NUM_OF_WORKERS redis workers
     // Each of them handles one TodoJob at a time
     let thread_log = log.clone();
+    let worker_config = WorkerConfig::from_config(&config, secrets.clone(), endpoint_policy.clone(), client_cert_dir);
     let mut worker_handlers:Vec<actix_web::rt::task::JoinHandle<()>> = Vec::new();
     for _i in 0..config.num_of_workers {
-        worker_handlers.push(actix_web::rt::spawn(redis_workers::worker::start_worker(options.clone(), thread_log.clone(), config.fail_percentage_treshold)));
+        worker_handlers.push(actix_web::rt::spawn(redis_workers::worker::start_worker(backend.clone(), thread_log.clone(), worker_config.clone())));
     }
 
     let result = HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(AppState{
                 log: log.clone(),
-                redis_options: options.clone()
+                queue: backend.queue.clone(),
+                store: backend.store.clone(),
+                secrets: secrets.clone(),
+                api_keys: api_keys.clone(),
+                quotas: quotas.clone(),
+                endpoint_policy: endpoint_policy.clone(),
+                job_limits: job_limits.clone()
             }))
-            .wrap(middleware::Logger::default())
+            .wrap(request_id::RequestIds)
+            .wrap(middleware::Logger::new("%a \"%r\" %s %b \"%{Referer}i\" \"%{User-Agent}i\" %T %{x-request-id}o"))
             .service(health::health)
-            .service(handlers::get_job)
-            .service(handlers::new_job)
+            .service(openapi::openapi)
+            // Every route but /health and the OpenAPI document requires an API key
+            .service(
+                web::scope("/v1")
+                    .wrap(auth::ApiKeyAuth)
+                    .service(handlers::get_job)
+                    .service(handlers::new_job)
+            )
+            // Unversioned routes, kept for the clients that predate /v1
+            .service(
+                web::scope("")
+                    .wrap(auth::ApiKeyAuth)
+                    .service(handlers::get_job)
+                    .service(handlers::new_job)
+            )
     })
     .bind(format!("{}:{}", config.server_host, config.server_port))
     .unwrap()
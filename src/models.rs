@@ -1,28 +1,702 @@
+use hyper::body::Bytes;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use slog;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::chains::Chain;
+use crate::endpoint_policy::EndpointPolicy;
+use crate::backend::{JobQueue, JobStore};
+use crate::secrets::SecretBox;
 
 #[derive(Clone)]
 pub struct AppState {
     pub log: slog::Logger,
-    pub redis_options: rsmq_async::RsmqOptions
+    pub queue: Arc<dyn JobQueue>,
+    pub store: Arc<dyn JobStore>,
+    pub secrets: SecretBox,
+    // Config's API keys, {api_key: client_name}
+    pub api_keys: HashMap<String, String>,
+    pub quotas: Quotas,
+    pub endpoint_policy: EndpointPolicy,
+    pub job_limits: JobLimits
+}
+
+// Config's bounds of every job's parameters
+#[derive(Clone, Debug)]
+pub struct JobLimits {
+    pub max_threads: u32,
+    pub max_duration: u32,
+    pub max_compare_endpoints: u32,
+    pub max_batch_size: u32,
+    pub max_ws_connections: u32,
+    pub max_ws_pipeline_depth: u32,
+    // Config's canonical CLIENT_CERT_DIR, None => no client certificates
+    pub client_cert_dir: Option<PathBuf>
+}
+
+// Config's per client limits, 0 => unlimited
+#[derive(Clone, Debug, Default)]
+pub struct Quotas {
+    pub max_concurrent_jobs: u32,
+    pub max_threads: u32,
+    pub max_duration: u32,
+    pub daily_requests: u64
+}
+
+// What the API reserved of the client's Quotas for a job: one of its concurrent jobs
+// and, with a daily limit, the job's max_requests on the given day
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UsageReservation {
+    // Member of the client's active jobs set
+    pub id: String,
+    pub day: u64
+}
+
+// Redis keys of a client's usage, counted against its Quotas
+pub struct ClientUsage;
+
+impl ClientUsage {
+    // Client that submitted the job, only that client can read the job's status and result
+    pub fn job_owner_key(job_id: &str) -> String {
+        format!("{}:owner", job_id)
+    }
+
+    // Set of the client's pending and running jobs, as the ids of their UsageReservation
+    pub fn active_jobs_key(client: &str) -> String {
+        format!("quota:{}:active_jobs", client)
+    }
+
+    // Requests reserved (then sent) by the client's jobs on a given day, as days since the Unix epoch (UTC)
+    pub fn daily_requests_key(client: &str, day: u64) -> String {
+        format!("quota:{}:requests:{}", client, day)
+    }
+
+    pub fn today() -> u64 {
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs() / 86400).unwrap_or(0)
+    }
 }
 
+// Seeds stay below 2^53, the largest integers JSON clients parsing numbers as doubles (JS) round-trip
+pub const MAX_SEED: u64 = 1 << 53;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct TodoJob {
-    // Either "EVM" or "BTC"
-    pub chain: String,
+    // One of "EVM", "BTC", "SOL", "COSMOS" or "SUBSTRATE"
+    pub chain: Chain,
     // Ex: https://endpoints.omniatech.io/v1/btc/mainnet/test
+    #[serde(alias = "endpoint")]
     pub endpoint_url: String,
     // How many threads to make concurrent requests to the endpoint_url
+    #[serde(alias = "threads")]
     pub num_threads: u32,
     pub duration: u32,
     // Auth token for endpoint (can be null/not provided)
-    pub authorization: Option<String>
+    pub authorization: Option<String>,
+    // Extra headers sent with every request, Ex: {"x-api-key": "<key>"}
+    pub headers: Option<HashMap<String, String>>,
+    // Query-string parameters added to every request, Ex: {"apikey": "<key>"}
+    pub query_params: Option<HashMap<String, String>>,
+    // mTLS: PEM client certificate and its PKCS#8 PEM private key, read from the worker's filesystem
+    pub client_cert_path: Option<String>,
+    pub client_key_path: Option<String>,
+    // COSMOS only: LCD REST base url, adds REST GET calls to the mix (can be null/not provided)
+    // Ex: https://endpoints.omniatech.io/v1/cosmos/mainnet/rest
+    pub rest_endpoint_url: Option<String>,
+    // "HTTP" or "WS", when not provided ws:// and wss:// endpoint_urls use WS and everything else HTTP
+    pub transport: Option<Transport>,
+    // WS only: persistent connections opened by each thread (default 1)
+    pub ws_connections: Option<u32>,
+    // WS only: max requests sent on a connection that still await their response (default 8)
+    pub ws_pipeline_depth: Option<u32>,
+    // "LOAD" (default) or "SUBSCRIPTION"
+    pub job_type: Option<JobType>,
+    // SUBSCRIPTION only: eth_subscribe kinds opened on each of the num_threads connections,
+    // any of "newHeads", "logs", "newPendingTransactions" (default ["newHeads"])
+    pub subscriptions: Option<Vec<String>>,
+    // HTTP only: JSON-RPC calls sent per request as a batch array (default 1 => no batching)
+    pub batch_size: Option<u32>,
+    // HTTP only: "HTTP1" or "HTTP2" forces the protocol version (default negotiated through TLS ALPN)
+    pub http_version: Option<HttpVersion>,
+    // HTTP only: false opens a new TCP (+TLS) connection for every request (default true)
+    pub keep_alive: Option<bool>,
+    // HTTP only: max connections to the endpoint, shared by all threads (default unlimited)
+    pub max_connections: Option<u32>,
+    // HTTP only: TCP_NODELAY on the job's connections (default true)
+    pub tcp_nodelay: Option<bool>,
+    // Timeouts of every request of the job, in milliseconds (defaults from the service's Config)
+    pub connect_timeout_ms: Option<u64>,
+    pub request_timeout_ms: Option<u64>,
+    // LOAD only: seconds of load sent before the duration starts, their measurements are discarded (default 0)
+    pub warmup: Option<u32>,
+    // LOAD only: other endpoints sent the same payload sequence as endpoint_url, for a side-by-side result
+    pub compare_endpoints: Option<Vec<CompareEndpoint>>,
+    // "SEQUENTIAL" (default) or "CONCURRENT" run of endpoint_url and compare_endpoints
+    pub compare_mode: Option<CompareMode>,
+    // LOAD only: seed of the weighted random payload selection, the same seed sends
+    // the same request sequence (default random below 2^53, reported in the job's result)
+    pub seed: Option<u64>,
+    // LOAD only: false skips the pre-flight seeding, the payloads are built from the chain's
+    // default blocks, txs and addresses so the same seed sends the same requests on every run (default true)
+    pub preflight_seeding: Option<bool>,
+    // Set by the API only: reference to the job's JobCredentials, stored encrypted in Redis
+    #[schemars(skip)]
+    pub credentials: Option<String>,
+    // Set by the API only: name of the client that submitted the job
+    #[schemars(skip)]
+    pub client: Option<String>,
+    // Set by the API only: the client's daily requests reserved for the job, it stops sending once it used them up
+    #[schemars(skip)]
+    pub max_requests: Option<u64>,
+    // Set by the API only: released by the redis-worker that finishes the job
+    #[schemars(skip)]
+    pub reservation: Option<UsageReservation>
+}
+
+impl TodoJob {
+    pub fn job_type(&self) -> JobType {
+        self.job_type.unwrap_or(JobType::Load)
+    }
+
+    pub fn preflight_seeding(&self) -> bool {
+        self.preflight_seeding.unwrap_or(true)
+    }
+
+    pub fn transport(&self) -> Transport {
+        match self.transport {
+            Some(t) => t,
+            None if self.endpoint_url.starts_with("ws://") || self.endpoint_url.starts_with("wss://") => Transport::Ws,
+            None => Transport::Http
+        }
+    }
+
+    // endpoint_url and its compare_endpoints
+    pub fn endpoints(&self) -> u64 {
+        1 + self.compare_endpoints.as_ref().map_or(0, |endpoints| endpoints.len() as u64)
+    }
+
+    // Threads running at the same time, a CONCURRENT compare job runs num_threads per endpoint
+    pub fn total_threads(&self) -> u64 {
+        match self.compare_mode {
+            Some(CompareMode::Concurrent) => self.num_threads as u64 * self.endpoints(),
+            _ => self.num_threads as u64
+        }
+    }
+
+    // Seconds the job sends load for, warm-up included, a SEQUENTIAL compare job runs the endpoints one after the other
+    pub fn total_duration(&self) -> u64 {
+        let duration = self.duration as u64 + self.warmup.unwrap_or(0) as u64;
+        match self.compare_mode {
+            Some(CompareMode::Concurrent) => duration,
+            _ => duration * self.endpoints()
+        }
+    }
+
+    // The job as run against each of its compare_endpoints
+    pub fn compared_jobs(&self) -> Vec<TodoJob> {
+        self.compare_endpoints.iter().flatten().map(|endpoint| TodoJob {
+            endpoint_url: endpoint.endpoint_url.clone(),
+            authorization: endpoint.authorization.clone(),
+            headers: endpoint.headers.clone(),
+            query_params: endpoint.query_params.clone(),
+            rest_endpoint_url: endpoint.rest_endpoint_url.clone(),
+            client_cert_path: None,
+            client_key_path: None,
+            compare_endpoints: None,
+            ..self.clone()
+        }).collect()
+    }
+
+    // Every url the job sends requests to
+    pub fn target_urls(&self) -> Vec<&str> {
+        let mut urls = vec![self.endpoint_url.as_str()];
+        if let Some(rest_endpoint_url) = &self.rest_endpoint_url {
+            urls.push(rest_endpoint_url.as_str());
+        }
+        for endpoint in self.compare_endpoints.iter().flatten() {
+            urls.push(endpoint.endpoint_url.as_str());
+            if let Some(rest_endpoint_url) = &endpoint.rest_endpoint_url {
+                urls.push(rest_endpoint_url.as_str());
+            }
+        }
+        urls
+    }
+
+    // Moves authorization, headers and query_params (compare_endpoints' included) out of the job,
+    // so it can travel through RSMQ without them
+    pub fn take_credentials(&mut self) -> JobCredentials {
+        JobCredentials {
+            authorization: self.authorization.take(),
+            headers: self.headers.take(),
+            query_params: self.query_params.take(),
+            compare_endpoints: self.compare_endpoints.iter_mut().flatten().map(|endpoint| JobCredentials {
+                authorization: endpoint.authorization.take(),
+                headers: endpoint.headers.take(),
+                query_params: endpoint.query_params.take(),
+                compare_endpoints: Vec::new()
+            }).collect()
+        }
+    }
+
+    pub fn restore_credentials(&mut self, credentials: JobCredentials) {
+        self.authorization = credentials.authorization;
+        self.headers = credentials.headers;
+        self.query_params = credentials.query_params;
+        for (endpoint, credentials) in self.compare_endpoints.iter_mut().flatten().zip(credentials.compare_endpoints) {
+            endpoint.authorization = credentials.authorization;
+            endpoint.headers = credentials.headers;
+            endpoint.query_params = credentials.query_params;
+        }
+    }
+
+    // Adds the job's credentials (Authorization, custom headers, query-string keys) to an HTTP request
+    pub fn authenticate(&self, mut request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        if let Some(authorization) = &self.authorization {
+            request = request.header("Authorization", authorization);
+        }
+        for (name, value) in self.headers.iter().flatten() {
+            request = request.header(name, value);
+        }
+        if let Some(query_params) = &self.query_params {
+            request = request.query(query_params);
+        }
+        request
+    }
+
+    // Url for the HTTP requests of the job (e.g. pre-flight), WS endpoints
+    // are assumed to serve plain JSON-RPC over HTTP on the same host and path
+    pub fn http_url(&self) -> String {
+        if let Some(rest) = self.endpoint_url.strip_prefix("wss://") {
+            return format!("https://{}", rest);
+        }
+        if let Some(rest) = self.endpoint_url.strip_prefix("ws://") {
+            return format!("http://{}", rest);
+        }
+        self.endpoint_url.clone()
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transport {
+    #[serde(rename = "HTTP")]
+    Http,
+    #[serde(rename = "WS")]
+    Ws
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HttpVersion {
+    #[serde(rename = "HTTP1")]
+    Http1,
+    #[serde(rename = "HTTP2")]
+    Http2
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobType {
+    // Request/response load on the endpoint
+    #[serde(rename = "LOAD")]
+    Load,
+    // WS eth_subscribe notifications delivered by the endpoint
+    #[serde(rename = "SUBSCRIPTION")]
+    Subscription
+}
+
+// Credentials of a job, never part of the RSMQ message: the API stores them
+// encrypted at JobCredentials::redis_key and the worker deletes them with the job
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct JobCredentials {
+    pub authorization: Option<String>,
+    pub headers: Option<HashMap<String, String>>,
+    pub query_params: Option<HashMap<String, String>>,
+    // In the order of the job's compare_endpoints
+    pub compare_endpoints: Vec<JobCredentials>
+}
+
+impl JobCredentials {
+    pub fn redis_key(reference: &str) -> String {
+        format!("credentials:{}", reference)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.authorization.is_none() && self.headers.is_none() && self.query_params.is_none()
+            && self.compare_endpoints.iter().all(|credentials| credentials.is_empty())
+    }
+}
+
+// Never print the credentials
+impl fmt::Debug for JobCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "JobCredentials(<redacted>)")
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct CompareEndpoint {
+    pub endpoint_url: String,
+    // COSMOS only: required when the job has a rest_endpoint_url, the LCD REST calls go there
+    pub rest_endpoint_url: Option<String>,
+    // Credentials for this endpoint only, the job's authorization, headers,
+    // query_params and client certificate aren't sent to compared endpoints
+    pub authorization: Option<String>,
+    pub headers: Option<HashMap<String, String>>,
+    pub query_params: Option<HashMap<String, String>>
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompareMode {
+    // One endpoint after the other, each gets the whole machine
+    #[serde(rename = "SEQUENTIAL")]
+    Sequential,
+    // All endpoints at the same time, under the same network conditions
+    #[serde(rename = "CONCURRENT")]
+    Concurrent
+}
+
+// Counters of one of the job's threads
+#[derive(Clone, Debug, Default)]
+pub struct ThreadStats {
+    pub ok_s: u64,
+    pub fails: u64,
+    // Batch mode only: ok_s/fails count the calls, these the batch requests
+    pub batches: u64,
+    pub batch_fails: u64,
+    pub errors: ErrorCounts,
+    // Requests still in flight at the job's deadline, neither ok nor failed
+    pub cancelled: u64,
+    // Set for the whole job, not per thread
+    pub connections_opened: Option<u64>,
+    pub duration_s: f64,
+    // Round-trip time of every answered request
+    pub latencies_ms: Vec<f64>
+}
+
+impl ThreadStats {
+    pub fn merge(&mut self, other: ThreadStats) {
+        self.ok_s += other.ok_s;
+        self.fails += other.fails;
+        self.batches += other.batches;
+        self.batch_fails += other.batch_fails;
+        self.errors.merge(&other.errors);
+        self.cancelled += other.cancelled;
+        self.latencies_ms.extend(other.latencies_ms);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    Timeout,
+    ConnectionRefused,
+    Tls,
+    Dns,
+    // Non 2xx HTTP response
+    HttpStatus,
+    // Answered request the chain's profile considers failed
+    JsonRpc,
+    Other
+}
+
+// Failed requests per category
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default, PartialEq)]
+pub struct ErrorCounts {
+    pub timeout: u64,
+    pub connection_refused: u64,
+    pub tls: u64,
+    pub dns: u64,
+    pub http_status: u64,
+    pub jsonrpc: u64,
+    pub other: u64
+}
+
+impl ErrorCounts {
+    pub fn add(&mut self, kind: ErrorKind, count: u64) {
+        match kind {
+            ErrorKind::Timeout => self.timeout += count,
+            ErrorKind::ConnectionRefused => self.connection_refused += count,
+            ErrorKind::Tls => self.tls += count,
+            ErrorKind::Dns => self.dns += count,
+            ErrorKind::HttpStatus => self.http_status += count,
+            ErrorKind::JsonRpc => self.jsonrpc += count,
+            ErrorKind::Other => self.other += count
+        }
+    }
+
+    pub fn merge(&mut self, other: &ErrorCounts) {
+        self.timeout += other.timeout;
+        self.connection_refused += other.connection_refused;
+        self.tls += other.tls;
+        self.dns += other.dns;
+        self.http_status += other.http_status;
+        self.jsonrpc += other.jsonrpc;
+        self.other += other.other;
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default, PartialEq)]
+pub struct LatencyStats {
+    pub avg: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64
+}
+
+impl LatencyStats {
+    pub fn from_samples(samples: &mut [f64]) -> LatencyStats {
+        if samples.is_empty() {
+            return LatencyStats::default();
+        }
+        samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let percentile = |p: f64| samples[((samples.len() - 1) as f64 * p).round() as usize];
+        LatencyStats {
+            avg: samples.iter().sum::<f64>() / samples.len() as f64,
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+            p99: percentile(0.99),
+            max: samples[samples.len() - 1]
+        }
+    }
+}
+
+// Details of a finished job, stored in Redis as JSON next to the job's status
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct JobResult {
+    pub transport: Transport,
+    pub requests: u64,
+    pub fails: u64,
+    pub errors: ErrorCounts,
+    // Requests cut off by the job's deadline, not part of requests
+    pub cancelled: u64,
+    // Measured from the first request sent to the deadline
+    pub duration_s: f64,
+    pub requests_per_second: f64,
+    pub latency_ms: LatencyStats,
+    // Client that submitted the job
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    // LOAD only: seed of the job's payload selection, to re-run the same request sequence
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    // HTTP only: TCP connections opened by the job's threads (endpoints with a hostname only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connections_opened: Option<u64>,
+    // Batch mode only, requests/fails then count the calls inside the batches
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batches: Option<BatchResult>,
+    // SUBSCRIPTION jobs only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subscriptions: Option<SubscriptionResult>,
+    // Jobs with compare_endpoints only, the result of each compared endpoint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comparison: Option<Vec<EndpointResult>>
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct EndpointResult {
+    pub endpoint_url: String,
+    #[serde(flatten)]
+    pub result: JobResult
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct BatchResult {
+    pub batch_size: u32,
+    pub batches: u64,
+    // Batches with at least one failed or missing call
+    pub fails: u64,
+    pub batches_per_second: f64,
+    pub calls_per_second: f64
+}
+
+impl JobResult {
+    pub fn redis_key(job_id: &str) -> String {
+        format!("{}:result", job_id)
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct JsonRpcMethod {
-    pub payload: &'static str,
+    pub payload: RpcPayload,
     pub weight: u32
-}
\ No newline at end of file
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RpcPayload {
+    // JSON-RPC body POSTed to the job's endpoint_url, shared by every request sending it
+    Post(Bytes),
+    // Path and query of a GET request to the job's endpoint_url (Tendermint URI calls)
+    Get(String),
+    // Path and query of a GET request to the job's rest_endpoint_url (Cosmos LCD REST)
+    RestGet(String)
+}
+
+impl RpcPayload {
+    // JSON-RPC body of a POST payload
+    pub fn body(&self) -> Option<&str> {
+        match self {
+            RpcPayload::Post(body) => std::str::from_utf8(body).ok(),
+            RpcPayload::Get(_) | RpcPayload::RestGet(_) => None
+        }
+    }
+
+    // Url of a GET payload for this job, so compared endpoints get the calls on their own urls
+    pub fn get_url(&self, job: &TodoJob) -> Option<String> {
+        let (base, path) = match self {
+            RpcPayload::Post(_) => return None,
+            RpcPayload::Get(path) => (job.endpoint_url.as_str(), path),
+            RpcPayload::RestGet(path) => (job.rest_endpoint_url.as_deref()?, path)
+        };
+        Some(format!("{}{}", base.trim_end_matches('/'), path))
+    }
+}
+
+impl From<&str> for RpcPayload {
+    fn from(body: &str) -> RpcPayload {
+        RpcPayload::Post(Bytes::copy_from_slice(body.as_bytes()))
+    }
+}
+
+impl From<String> for RpcPayload {
+    fn from(body: String) -> RpcPayload {
+        RpcPayload::Post(Bytes::from(body))
+    }
+}
+
+// Real chain data collected from the target endpoint before a job starts,
+// used to fill in the params of the generated JSON-RPC payloads
+#[derive(Clone, Debug, Default)]
+pub struct ChainSeed {
+    pub block_numbers: Vec<u64>,
+    pub block_hashes: Vec<String>,
+    pub tx_hashes: Vec<String>,
+    pub addresses: Vec<String>,
+    // BTC only: (txid, vout) pairs of recent transaction outputs
+    pub outpoints: Vec<(String, u32)>
+}
+
+// Counters of one of a SUBSCRIPTION job's connections
+#[derive(Clone, Debug, Default)]
+pub struct SubscriptionStats {
+    pub notifications: u64,
+    pub duplicates: u64,
+    // newHeads only: block numbers skipped between two notifications
+    pub dropped: u64,
+    pub disconnects: u64,
+    // Connection + eth_subscribe confirmation time, once per (re)connect
+    pub setup_ms: Vec<f64>,
+    // newHeads only: notification arrival minus the block's timestamp
+    pub delivery_ms: Vec<f64>
+}
+
+impl SubscriptionStats {
+    pub fn merge(&mut self, other: SubscriptionStats) {
+        self.notifications += other.notifications;
+        self.duplicates += other.duplicates;
+        self.dropped += other.dropped;
+        self.disconnects += other.disconnects;
+        self.setup_ms.extend(other.setup_ms);
+        self.delivery_ms.extend(other.delivery_ms);
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct SubscriptionResult {
+    pub notifications: u64,
+    pub duplicates: u64,
+    pub dropped: u64,
+    pub disconnects: u64,
+    pub setup_ms: LatencyStats,
+    pub delivery_ms: LatencyStats
+}
+
+// Response of POST /v1/jobs
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct NewJobResponse {
+    pub id: String
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum JobStatus {
+    Pending,
+    Errored,
+    Finished
+}
+
+// Response of GET /v1/jobs/{job_id}, rps and result are set once the job is FINISHED
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct JobStatusResponse {
+    pub status: JobStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rps: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<JobResult>
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::{LatencyStats, RpcPayload, TodoJob, Transport};
+
+
+    #[test]
+    fn test_transport_defaults_to_endpoint_scheme() {
+        let job: TodoJob = serde_json::from_str(r#"{"chain": "EVM", "endpoint_url": "wss://localhost/ws", "num_threads": 1, "duration": 1}"#).unwrap();
+        assert_eq!(job.transport(), Transport::Ws);
+        assert_eq!(job.http_url(), "https://localhost/ws");
+    }
+
+    #[test]
+    fn test_documented_field_names_are_accepted() {
+        let job: TodoJob = serde_json::from_str(r#"{"chain": "EVM", "endpoint": "https://localhost/rpc", "threads": 20, "duration": 1}"#).unwrap();
+        assert_eq!(job.endpoint_url, "https://localhost/rpc");
+        assert_eq!(job.num_threads, 20);
+    }
+
+    #[test]
+    fn test_latency_percentiles() {
+        let mut samples: Vec<f64> = (1..=100).rev().map(|s| s as f64).collect();
+        let stats = LatencyStats::from_samples(&mut samples);
+        assert_eq!(stats.p50, 51.0);
+        assert_eq!(stats.p99, 99.0);
+        assert_eq!(stats.max, 100.0);
+    }
+
+    #[test]
+    fn test_authenticate_adds_headers_and_query_params() {
+        let job: TodoJob = serde_json::from_str(r#"{"chain": "EVM", "endpoint_url": "https://localhost/rpc", "num_threads": 1, "duration": 1,
+            "authorization": "Bearer t", "headers": {"x-api-key": "k"}, "query_params": {"apikey": "q"}}"#).unwrap();
+        let request = job.authenticate(reqwest::Client::new().post(job.endpoint_url.clone())).build().unwrap();
+        assert_eq!(request.url().as_str(), "https://localhost/rpc?apikey=q");
+        assert_eq!(request.headers()["Authorization"], "Bearer t");
+        assert_eq!(request.headers()["x-api-key"], "k");
+    }
+
+    #[test]
+    fn test_credentials_are_taken_out_of_the_job() {
+        let mut job: TodoJob = serde_json::from_str(r#"{"chain": "EVM", "endpoint_url": "https://localhost/rpc", "num_threads": 1, "duration": 1,
+            "authorization": "Bearer t", "compare_endpoints": [{"endpoint_url": "https://provider/rpc", "headers": {"x-api-key": "k"}}]}"#).unwrap();
+        let credentials = job.take_credentials();
+        assert!(!credentials.is_empty());
+        assert!(!serde_json::to_string(&job).unwrap().contains("Bearer"));
+        assert!(!format!("{:?}", credentials).contains("Bearer"));
+
+        job.restore_credentials(credentials);
+        assert_eq!(job.authorization.as_deref(), Some("Bearer t"));
+        assert_eq!(job.compared_jobs()[0].headers.as_ref().unwrap()["x-api-key"], "k");
+    }
+
+    #[test]
+    fn test_get_payloads_target_each_compared_endpoint() {
+        let job: TodoJob = serde_json::from_str(r#"{"chain": "COSMOS", "endpoint_url": "https://primary/rpc/", "rest_endpoint_url": "https://primary/rest",
+            "num_threads": 1, "duration": 1, "compare_endpoints": [{"endpoint_url": "https://other/rpc", "rest_endpoint_url": "https://other/rest"}]}"#).unwrap();
+        let compared_job = &job.compared_jobs()[0];
+        assert_eq!(RpcPayload::Get(String::from("/block?height=1")).get_url(&job).unwrap(), "https://primary/rpc/block?height=1");
+        assert_eq!(RpcPayload::Get(String::from("/block?height=1")).get_url(compared_job).unwrap(), "https://other/rpc/block?height=1");
+        assert_eq!(RpcPayload::RestGet(String::from("/cosmos/tx/v1beta1/txs/ab")).get_url(compared_job).unwrap(), "https://other/rest/cosmos/tx/v1beta1/txs/ab");
+        assert_eq!(RpcPayload::from("{}").get_url(&job), None);
+    }
+}
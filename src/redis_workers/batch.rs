@@ -0,0 +1,185 @@
+use serde_json::{json, Value};
+use slog::Logger;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::{timeout_at, Instant};
+
+use crate::chains::ChainProfile;
+use crate::models;
+use crate::redis_workers::{budget::RequestBudget, failures, http_client::JobClient};
+
+
+// Batch counterpart of worker::execute_job: every request is a JSON-RPC batch
+// of job.batch_size calls taken from the circular list of payloads
+pub async fn execute_job(
+    _log: &Logger,
+    job: &models::TodoJob,
+    start: &Instant,
+    http: &JobClient,
+    budget: &RequestBudget,
+    rpc_payloads: &[Arc<models::RpcPayload>]
+) -> models::ThreadStats {
+    let profile = job.chain.profile();
+    let batch_size = job.batch_size.unwrap_or(1) as usize;
+    // GET payloads (e.g. Cosmos LCD REST) can't be part of a JSON-RPC batch
+    let bodies: Vec<&str> = rpc_payloads.iter().filter_map(|payload| payload.body()).collect();
+    let mut stats = models::ThreadStats::default();
+    if bodies.is_empty() {
+        return stats;
+    }
+    let deadline = *start + Duration::from_secs(job.duration as u64);
+    let mut warming_up = Instant::now() < *start;
+    let mut rpc_payload_index = 0;
+    loop {
+        if Instant::now() >= deadline {
+            break;
+        }
+        // Discards what was measured during the warm-up
+        if warming_up && Instant::now() >= *start {
+            stats = models::ThreadStats::default();
+            warming_up = false;
+        }
+        let _permit = match timeout_at(deadline, http.acquire()).await {
+            Ok(permit) => permit,
+            Err(_) => break
+        };
+        let calls = batch_size as u64;
+        // The client's daily requests are used up, checked before the batch takes up any memory
+        if !budget.take(calls) {
+            break;
+        }
+        let (batch, ids) = build_batch(&bodies, &mut rpc_payload_index, batch_size);
+        let request = job.authenticate(http.client.post(job.endpoint_url.clone())
+                                .body(batch)
+                                .header("Content-Type", "application/json"));
+        let sent = Instant::now();
+        // The whole batch is cancelled when the deadline hits while it is in flight
+        let response = match timeout_at(deadline, request.send()).await {
+            Ok(response) => response,
+            Err(_) => {
+                stats.cancelled += calls;
+                break;
+            }
+        };
+        let (ok_calls, failed_calls) = match response {
+            Ok(response) if response.status().is_success() => {
+                let body = match timeout_at(deadline, response.text()).await {
+                    Ok(body) => body,
+                    Err(_) => {
+                        stats.cancelled += calls;
+                        break;
+                    }
+                };
+                let result = match body {
+                    Ok(body) => {
+                        // Missing or failed calls of an answered batch
+                        let (ok_calls, failed_calls) = check_batch_response(profile, body.as_str(), &ids);
+                        stats.errors.add(models::ErrorKind::JsonRpc, failed_calls);
+                        (ok_calls, failed_calls)
+                    },
+                    Err(e) => {
+                        stats.errors.add(failures::classify_request_error(&e), calls);
+                        (0, calls)
+                    }
+                };
+                stats.latencies_ms.push(sent.elapsed().as_secs_f64() * 1000.0);
+                result
+            },
+            Ok(_) => {
+                stats.errors.add(models::ErrorKind::HttpStatus, calls);
+                (0, calls)
+            },
+            // counts "No response -> connection closed" errors
+            Err(e) => {
+                stats.errors.add(failures::classify_request_error(&e), calls);
+                (0, calls)
+            }
+        };
+        stats.ok_s += ok_calls;
+        stats.fails += failed_calls;
+        stats.batches += 1;
+        if failed_calls > 0 {
+            stats.batch_fails += 1;
+        }
+    }
+    stats
+}
+
+// Builds a JSON-RPC batch array out of the next batch_size payloads, with ids 1..=batch_size
+fn build_batch(
+    bodies: &[&str],
+    rpc_payload_index: &mut usize,
+    batch_size: usize
+) -> (String, Vec<u64>) {
+    let mut batch: Vec<Value> = Vec::with_capacity(batch_size);
+    let mut ids: Vec<u64> = Vec::with_capacity(batch_size);
+    for id in 1..=batch_size as u64 {
+        // Basically turns bodies into a circular list
+        if *rpc_payload_index >= bodies.len() {
+            *rpc_payload_index = 0;
+        }
+        let mut call: Value = match serde_json::from_str(bodies[*rpc_payload_index]) {
+            Ok(c) => c,
+            Err(_) => json!({})
+        };
+        *rpc_payload_index += 1;
+        call["id"] = json!(id);
+        batch.push(call);
+        ids.push(id);
+    }
+    (Value::Array(batch).to_string(), ids)
+}
+
+// Returns (ok, failed) calls of a batch response, every sent id has to be answered exactly once
+fn check_batch_response(
+    profile: &dyn ChainProfile,
+    body: &str,
+    ids: &[u64]
+) -> (u64, u64) {
+    let responses = match serde_json::from_str::<Value>(body) {
+        Ok(Value::Array(responses)) => responses,
+        // e.g. a single error object when the node doesn't support batches
+        _ => return (0, ids.len() as u64)
+    };
+    let mut ok_calls = 0;
+    for id in ids {
+        let mut matching = responses.iter().filter(|response| response["id"].as_u64() == Some(*id));
+        let response = match (matching.next(), matching.next()) {
+            (Some(response), None) => response,
+            _ => continue // missing or duplicated id
+        };
+        // An element carrying an error is a failed call, unless the chain's nodes answer it when healthy
+        if profile.response_is_ok(response.to_string().as_str()) {
+            ok_calls += 1;
+        }
+    }
+    (ok_calls, ids.len() as u64 - ok_calls)
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::{build_batch, check_batch_response};
+    use crate::chains::Chain;
+
+
+    #[test]
+    fn test_batch_ids_are_correlated() {
+        let bodies = vec![r#"{"jsonrpc": "2.0", "id": 1, "method": "eth_blockNumber", "params": []}"#];
+        let mut index = 0;
+        let (batch, ids) = build_batch(&bodies, &mut index, 3);
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(batch.as_str()).unwrap().as_array().unwrap().len(), 3);
+
+        let profile = Chain::Evm.profile();
+        let response = r#"[{"jsonrpc":"2.0","id":2,"result":"0x1"},{"jsonrpc":"2.0","id":1,"result":"0x1"}]"#;
+        assert_eq!(check_batch_response(profile, response, &ids), (2, 1));
+        assert_eq!(check_batch_response(profile, r#"{"jsonrpc":"2.0","id":null,"error":{"code":-32600}}"#, &ids), (0, 3));
+
+        let response = r#"[{"jsonrpc":"2.0","id":1,"error":{"code":-32005,"message":"limit exceeded"}},
+            {"jsonrpc":"2.0","id":2,"error":{"code":-32000,"message":"nonce too low"}},{"jsonrpc":"2.0","id":3,"result":"0x1"}]"#;
+        assert_eq!(check_batch_response(profile, response, &ids), (2, 1));
+    }
+}
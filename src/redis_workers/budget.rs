@@ -0,0 +1,81 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+
+// Requests sent by a LOAD job, warm-up and compare_endpoints included, shared by all its threads.
+// They are counted against the client's daily requests, and the job stops sending once
+// it used up max_requests (the client's remaining daily requests when the job was submitted)
+#[derive(Clone, Debug)]
+pub struct RequestBudget {
+    sent: Arc<AtomicU64>,
+    max_requests: Option<u64>,
+    // Requests sent by the whole job, when the budget is one endpoint's part of it
+    job_sent: Option<Arc<AtomicU64>>
+}
+
+impl RequestBudget {
+    pub fn new(max_requests: Option<u64>) -> RequestBudget {
+        RequestBudget { sent: Arc::new(AtomicU64::new(0)), max_requests, job_sent: None }
+    }
+
+    // Equal parts of the budget for the endpoints of a compare job, so none of them can use up
+    // the others' requests. What they send is still counted by this budget
+    pub fn split(&self, parts: u64) -> Vec<RequestBudget> {
+        (0..parts).map(|_| RequestBudget {
+            sent: Arc::new(AtomicU64::new(0)),
+            max_requests: self.max_requests.map(|max_requests| max_requests / parts),
+            job_sent: Some(self.sent.clone())
+        }).collect()
+    }
+
+    // Counts requests about to be sent, false (and nothing counted) when they don't fit anymore
+    pub fn take(&self, requests: u64) -> bool {
+        let taken = match self.max_requests {
+            None => {
+                self.sent.fetch_add(requests, Ordering::Relaxed);
+                true
+            },
+            Some(max_requests) => self.sent
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |sent| Some(sent + requests).filter(|sent| *sent <= max_requests))
+                .is_ok()
+        };
+        if let (true, Some(job_sent)) = (taken, &self.job_sent) {
+            job_sent.fetch_add(requests, Ordering::Relaxed);
+        }
+        taken
+    }
+
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::RequestBudget;
+
+
+    #[test]
+    fn test_budget_is_shared_and_capped() {
+        let budget = RequestBudget::new(Some(10));
+        let thread_budget = budget.clone();
+        assert!(budget.take(6));
+        assert!(!thread_budget.take(5));
+        assert!(thread_budget.take(4));
+        assert!(!budget.take(1));
+        assert_eq!(budget.sent(), 10);
+
+        let job_budget = RequestBudget::new(Some(10));
+        let parts = job_budget.split(3);
+        assert!(parts[0].take(3));
+        assert!(!parts[0].take(1));
+        assert!(parts[1].take(2));
+        assert_eq!(job_budget.sent(), 5);
+
+        let unlimited = RequestBudget::new(None);
+        assert!(unlimited.take(u32::MAX as u64));
+        assert_eq!(unlimited.sent(), u32::MAX as u64);
+    }
+}
@@ -0,0 +1,42 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::endpoint_policy::EndpointPolicy;
+use crate::models;
+use crate::secrets::SecretBox;
+
+
+// Settings every redis-worker needs, taken from the service's Config
+#[derive(Clone, Debug)]
+pub struct WorkerConfig {
+    pub fail_percentage_treshold: f64,
+    pub connect_timeout_ms: u64,
+    pub request_timeout_ms: u64,
+    pub secrets: SecretBox,
+    pub endpoint_policy: EndpointPolicy,
+    // Config's canonical CLIENT_CERT_DIR
+    pub client_cert_dir: Option<PathBuf>
+}
+
+impl WorkerConfig {
+    pub fn from_config(config: &Config, secrets: SecretBox, endpoint_policy: EndpointPolicy, client_cert_dir: Option<PathBuf>) -> WorkerConfig {
+        WorkerConfig {
+            fail_percentage_treshold: config.fail_percentage_treshold,
+            connect_timeout_ms: config.connect_timeout_ms,
+            request_timeout_ms: config.request_timeout_ms,
+            secrets,
+            endpoint_policy,
+            client_cert_dir
+        }
+    }
+
+    // The job's own timeouts take precedence over the Config defaults
+    pub fn connect_timeout(&self, job: &models::TodoJob) -> Duration {
+        Duration::from_millis(job.connect_timeout_ms.unwrap_or(self.connect_timeout_ms))
+    }
+
+    pub fn request_timeout(&self, job: &models::TodoJob) -> Duration {
+        Duration::from_millis(job.request_timeout_ms.unwrap_or(self.request_timeout_ms))
+    }
+}
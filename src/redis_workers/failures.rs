@@ -0,0 +1,89 @@
+use std::error::Error;
+use std::io;
+
+use tokio_tungstenite::tungstenite;
+
+use crate::models::ErrorKind;
+
+
+// Sorts a failed HTTP request into one of the reported error categories
+pub fn classify_request_error(error: &reqwest::Error) -> ErrorKind {
+    if error.is_timeout() {
+        return ErrorKind::Timeout;
+    }
+    if error.is_status() {
+        return ErrorKind::HttpStatus;
+    }
+    // reqwest/hyper only tell the cause apart through their error sources
+    let mut source: Option<&(dyn Error + 'static)> = Some(error);
+    while let Some(e) = source {
+        if let Some(io_error) = e.downcast_ref::<io::Error>() {
+            let kind = classify_io_error(io_error);
+            if kind != ErrorKind::Other {
+                return kind;
+            }
+        }
+        let kind = classify_message(e.to_string().as_str());
+        if kind != ErrorKind::Other {
+            return kind;
+        }
+        source = e.source();
+    }
+    ErrorKind::Other
+}
+
+// Sorts a failed WS connection into one of the reported error categories
+pub fn classify_ws_error(error: &tungstenite::Error) -> ErrorKind {
+    match error {
+        tungstenite::Error::Io(io_error) => classify_io_error(io_error),
+        tungstenite::Error::Tls(_) => ErrorKind::Tls,
+        tungstenite::Error::Http(_) => ErrorKind::HttpStatus,
+        e => classify_message(e.to_string().as_str())
+    }
+}
+
+pub fn classify_io_error(error: &io::Error) -> ErrorKind {
+    match error.kind() {
+        io::ErrorKind::TimedOut => ErrorKind::Timeout,
+        io::ErrorKind::ConnectionRefused => ErrorKind::ConnectionRefused,
+        _ => classify_message(error.to_string().as_str())
+    }
+}
+
+pub fn classify_message(message: &str) -> ErrorKind {
+    let message = message.to_lowercase();
+    if message.contains("dns error") || message.contains("lookup address") || message.contains("name or service not known") {
+        return ErrorKind::Dns;
+    }
+    if message.contains("tls") || message.contains("ssl") || message.contains("certificate") {
+        return ErrorKind::Tls;
+    }
+    if message.contains("connection refused") {
+        return ErrorKind::ConnectionRefused;
+    }
+    if message.contains("timed out") {
+        return ErrorKind::Timeout;
+    }
+    ErrorKind::Other
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::classify_ws_error;
+    use crate::models::ErrorKind;
+    use std::io;
+    use tokio_tungstenite::tungstenite;
+
+
+    #[test]
+    fn test_classify_io_errors() {
+        let refused = tungstenite::Error::Io(io::Error::new(io::ErrorKind::ConnectionRefused, "refused"));
+        assert_eq!(classify_ws_error(&refused), ErrorKind::ConnectionRefused);
+        let dns = tungstenite::Error::Io(io::Error::other("failed to lookup address information: Name or service not known"));
+        assert_eq!(classify_ws_error(&dns), ErrorKind::Dns);
+        let reset = tungstenite::Error::Io(io::Error::new(io::ErrorKind::ConnectionReset, "reset by peer"));
+        assert_eq!(classify_ws_error(&reset), ErrorKind::Other);
+    }
+}
@@ -0,0 +1,180 @@
+use std::net::SocketAddr;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::endpoint_policy::EndpointPolicy;
+use crate::models;
+use crate::redis_workers::config::WorkerConfig;
+
+
+// reqwest::Client configured with the job's HTTP protocol options,
+// shared by all of the job's threads
+#[derive(Clone)]
+pub struct JobClient {
+    pub client: reqwest::Client,
+    // Caps the requests in flight, hence the connections, when the job sets max_connections
+    limiter: Option<Arc<Semaphore>>,
+    // None when the endpoint is an IP address, hyper doesn't resolve those
+    connections_opened: Option<Arc<AtomicU64>>
+}
+
+impl JobClient {
+    pub fn new(job: &models::TodoJob, worker_config: &WorkerConfig) -> Result<JobClient, String> {
+        let connections_opened = Arc::new(AtomicU64::new(0));
+        let mut builder = reqwest::Client::builder()
+            .dns_resolver(Arc::new(CountingResolver {
+                connections_opened: connections_opened.clone(),
+                endpoint_policy: worker_config.endpoint_policy.clone()
+            }))
+            .redirect(redirect_policy(worker_config.endpoint_policy.clone()))
+            .connect_timeout(worker_config.connect_timeout(job))
+            .timeout(worker_config.request_timeout(job))
+            .tcp_nodelay(job.tcp_nodelay.unwrap_or(true));
+        // Over TLS the version is negotiated through ALPN, HTTP2 only offers h2.
+        // Over plain http HTTP2 is h2c with prior knowledge, there is no upgrade
+        builder = match job.http_version {
+            Some(models::HttpVersion::Http1) => builder.http1_only(),
+            Some(models::HttpVersion::Http2) => builder.http2_prior_knowledge(),
+            None => builder
+        };
+        if let Some(identity) = client_identity(job, worker_config.client_cert_dir.as_deref())? {
+            builder = builder.identity(reqwest::Identity::from_pkcs8_pem(&identity.cert, &identity.key).map_err(|e| e.to_string())?);
+        }
+        if let Some(max_connections) = job.max_connections {
+            builder = builder.pool_max_idle_per_host(max_connections as usize);
+        }
+        // Without idle connections in the pool every request opens a new TCP (+TLS) connection
+        if !job.keep_alive.unwrap_or(true) {
+            builder = builder.pool_max_idle_per_host(0);
+        }
+        Ok(JobClient {
+            client: builder.build().map_err(|e| e.to_string())?,
+            limiter: job.max_connections.map(|max_connections| Arc::new(Semaphore::new(max_connections as usize))),
+            connections_opened: Some(connections_opened).filter(|_| !has_ip_host(job.endpoint_url.as_str()))
+        })
+    }
+
+    // Held for as long as a request is in flight
+    pub async fn acquire(&self) -> Option<SemaphorePermit<'_>> {
+        match &self.limiter {
+            Some(limiter) => limiter.acquire().await.ok(),
+            None => None
+        }
+    }
+
+    pub fn connections_opened(&self) -> Option<u64> {
+        self.connections_opened.as_ref().map(|connections_opened| connections_opened.load(Ordering::Relaxed))
+    }
+}
+
+fn has_ip_host(url: &str) -> bool {
+    matches!(reqwest::Url::parse(url).ok().as_ref().and_then(|url| url.host()), Some(url::Host::Ipv4(_) | url::Host::Ipv6(_)))
+}
+
+// PEM client certificate and PKCS#8 PEM private key of the job's mTLS
+pub struct ClientIdentity {
+    pub cert: Vec<u8>,
+    pub key: Vec<u8>
+}
+
+pub fn client_identity(job: &models::TodoJob, client_cert_dir: Option<&Path>) -> Result<Option<ClientIdentity>, String> {
+    match (&job.client_cert_path, &job.client_key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            // Checked again, the files may have changed since the job was submitted
+            let cert = std::fs::read(resolve_cert_path(client_cert_dir, cert_path)?).map_err(|e| format!("Failed to read client_cert_path {}: {}", cert_path, e))?;
+            let key = std::fs::read(resolve_cert_path(client_cert_dir, key_path)?).map_err(|e| format!("Failed to read client_key_path {}: {}", key_path, e))?;
+            Ok(Some(ClientIdentity { cert, key }))
+        },
+        (None, None) => Ok(None),
+        _ => Err("client_cert_path and client_key_path must be provided together".to_string())
+    }
+}
+
+// Jobs can only read certificate files inside the Config's CLIENT_CERT_DIR (canonical):
+// paths are relative to it, absolute ones have to point inside it, no ".." and symlinks are resolved
+pub fn resolve_cert_path(client_cert_dir: Option<&Path>, path: &str) -> Result<PathBuf, String> {
+    let client_cert_dir = client_cert_dir.ok_or_else(|| "Client certificates are disabled, CLIENT_CERT_DIR is not set".to_string())?;
+    if Path::new(path).components().any(|component| component == Component::ParentDir) {
+        return Err(format!("{} must not contain ..", path));
+    }
+    let resolved = client_cert_dir.join(path).canonicalize().map_err(|_| format!("{} is not a file in CLIENT_CERT_DIR", path))?;
+    if !resolved.starts_with(client_cert_dir) || !resolved.is_file() {
+        return Err(format!("{} is not a file in CLIENT_CERT_DIR", path));
+    }
+    Ok(resolved)
+}
+
+// Redirects are followed (up to 10, as reqwest does by default) to allowed urls only
+fn redirect_policy(endpoint_policy: EndpointPolicy) -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(move |attempt| {
+        if let Err(e) = endpoint_policy.check_url(attempt.url().as_str()) {
+            attempt.error(e)
+        } else if attempt.previous().len() >= 10 {
+            attempt.stop()
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+// hyper resolves the endpoint's host once for every new connection, so counting
+// resolutions counts the connections opened (endpoints with a hostname only, not an IP).
+// Resolving here also keeps every connection off the addresses the EndpointPolicy denies
+struct CountingResolver {
+    connections_opened: Arc<AtomicU64>,
+    endpoint_policy: EndpointPolicy
+}
+
+impl Resolve for CountingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        self.connections_opened.fetch_add(1, Ordering::Relaxed);
+        let endpoint_policy = self.endpoint_policy.clone();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            let addrs = endpoint_policy.filter_addrs(name.as_str(), addrs);
+            if addrs.is_empty() {
+                return Err(format!("{} doesn't resolve to any allowed address", name.as_str()).into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::{has_ip_host, resolve_cert_path};
+
+
+    #[test]
+    fn test_ip_hosts_are_recognized() {
+        assert!(has_ip_host("https://203.0.113.7:8545/rpc"));
+        assert!(has_ip_host("http://[2001:db8::1]/"));
+        assert!(!has_ip_host("https://endpoints.omniatech.io/v1/eth"));
+    }
+
+    #[test]
+    fn test_cert_paths_stay_in_cert_dir() {
+        let cert_dir = std::env::temp_dir().join(format!("client_certs_{}", std::process::id()));
+        std::fs::create_dir_all(cert_dir.join("team-a")).unwrap();
+        std::fs::write(cert_dir.join("team-a/client.pem"), "cert").unwrap();
+        let cert_dir = cert_dir.canonicalize().unwrap();
+
+        assert_eq!(resolve_cert_path(Some(&cert_dir), "team-a/client.pem").unwrap(), cert_dir.join("team-a/client.pem"));
+        let absolute = cert_dir.join("team-a/client.pem");
+        assert!(resolve_cert_path(Some(&cert_dir), absolute.to_str().unwrap()).is_ok());
+        assert!(resolve_cert_path(Some(&cert_dir), "team-a/../team-a/client.pem").is_err());
+        assert!(resolve_cert_path(Some(&cert_dir), "/etc/passwd").is_err());
+        assert!(resolve_cert_path(Some(&cert_dir), "team-a").is_err());
+        assert!(resolve_cert_path(None, "team-a/client.pem").is_err());
+        std::fs::remove_dir_all(&cert_dir).unwrap();
+    }
+}
@@ -1,2 +1,9 @@
+pub mod batch;
+pub mod budget;
 pub mod config;
-pub mod worker;
\ No newline at end of file
+pub mod failures;
+pub mod http_client;
+pub mod worker;
+pub mod preflight;
+pub mod subscriptions;
+pub mod ws_transport;
\ No newline at end of file
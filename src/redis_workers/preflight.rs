@@ -0,0 +1,62 @@
+use reqwest;
+use slog::{info, warn, Logger};
+
+use crate::models;
+use crate::redis_workers::{config::WorkerConfig, ws_transport};
+
+
+// Checks the target endpoint answers the chain's health probe before any load is sent
+pub async fn probe_endpoint(
+    client: &reqwest::Client,
+    job: &models::TodoJob,
+    worker_config: &WorkerConfig,
+    log: &Logger
+) -> bool {
+    let profile = job.chain.profile();
+    let body = match job.transport() {
+        models::Transport::Ws => ws_transport::probe(job, worker_config, profile.health_probe()).await,
+        models::Transport::Http => {
+            let request = job.authenticate(client.post(job.endpoint_url.clone())
+                                    .body(profile.health_probe())
+                                    .header("Content-Type", "application/json"));
+            match request.send().await {
+                Ok(response) if response.status().is_success() => response.text().await.ok(),
+                _ => None
+            }
+        }
+    };
+    let healthy = match body {
+        Some(body) => !profile.validates_response_body() || profile.response_is_ok(body.as_str()),
+        None => false
+    };
+    if !healthy {
+        warn!(log, "Health probe failed for {} {}", job.chain, job.endpoint_url);
+    }
+    healthy
+}
+
+// Queries the target endpoint before the run to collect real, recent chain data
+// (block numbers, block hashes, tx hashes, addresses) for the payload generators.
+// On any failure the returned ChainSeed is empty and the generators fall back
+// to their hard-coded mainnet values.
+pub async fn seed_from_endpoint(
+    client: &reqwest::Client,
+    job: &models::TodoJob,
+    log: &Logger
+) -> models::ChainSeed {
+    match job.chain.profile().seed(client, job).await {
+        Some(seed) => {
+            info!(log, "Pre-flight seeded {} blocks, {} txs, {} addresses from {}",
+                seed.block_numbers.len(),
+                seed.tx_hashes.len(),
+                seed.addresses.len(),
+                job.endpoint_url
+            );
+            seed
+        },
+        None => {
+            warn!(log, "Pre-flight seeding failed for {}, using default payloads", job.endpoint_url);
+            models::ChainSeed::default()
+        }
+    }
+}
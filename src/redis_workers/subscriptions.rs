@@ -0,0 +1,221 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use slog::{warn, Logger};
+use tokio::time::{timeout, timeout_at, Instant};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::models;
+use crate::redis_workers::{config::WorkerConfig, ws_transport};
+
+pub const SUBSCRIPTION_KINDS: [&str; 3] = ["newHeads", "logs", "newPendingTransactions"];
+// How long the node has to confirm the eth_subscribe requests
+const SUBSCRIBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+
+// Opens one WS connection per num_threads, each subscribed to the job's subscription kinds,
+// and collects notifications until the job's duration is over.
+// Returned ThreadStats: ok_s => notifications, fails => duplicates + dropped + disconnects,
+// latencies => newHeads delivery latencies
+pub async fn run_job(
+    log: &Logger,
+    job: &models::TodoJob,
+    worker_config: &WorkerConfig
+) -> (models::ThreadStats, models::SubscriptionResult) {
+    let start = Instant::now();
+    let mut concurrent_threads_handlers: Vec<actix_web::rt::task::JoinHandle<models::SubscriptionStats>> = Vec::new();
+    for _i in 0..job.num_threads {
+        let thread_log = log.clone();
+        let job = job.clone();
+        let worker_config = worker_config.clone();
+        concurrent_threads_handlers.push(
+            actix_web::rt::spawn(
+                async move {
+                    execute_job(&thread_log, &job, &start, &worker_config).await
+                }
+            )
+        );
+    }
+    let join_results = futures::future::join_all(concurrent_threads_handlers).await;
+
+    let mut stats = models::SubscriptionStats::default();
+    for res in join_results.into_iter().flatten() {
+        stats.merge(res);
+    }
+    let thread_stats = models::ThreadStats {
+        duration_s: start.elapsed().as_secs_f64(),
+        ok_s: stats.notifications,
+        fails: stats.duplicates + stats.dropped + stats.disconnects,
+        latencies_ms: stats.delivery_ms.clone(),
+        ..Default::default()
+    };
+    let result = models::SubscriptionResult {
+        notifications: stats.notifications,
+        duplicates: stats.duplicates,
+        dropped: stats.dropped,
+        disconnects: stats.disconnects,
+        setup_ms: models::LatencyStats::from_samples(&mut stats.setup_ms),
+        delivery_ms: models::LatencyStats::from_samples(&mut stats.delivery_ms)
+    };
+    (thread_stats, result)
+}
+
+async fn execute_job(
+    log: &Logger,
+    job: &models::TodoJob,
+    start: &Instant,
+    worker_config: &WorkerConfig
+) -> models::SubscriptionStats {
+    let kinds = job.subscriptions.clone().unwrap_or_else(|| vec![String::from("newHeads")]);
+    let deadline = *start + Duration::from_secs(job.duration as u64);
+    let mut stats = models::SubscriptionStats::default();
+    // Kept across reconnects so re-delivered notifications count as duplicates
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut last_head: Option<u64> = None;
+
+    // Reconnects for as long as the job lasts
+    while Instant::now() < deadline {
+        let setup = Instant::now();
+        let ws = match timeout_at(deadline, ws_transport::connect(job, worker_config)).await {
+            Ok(Ok(ws)) => ws,
+            Ok(Err(_)) => {
+                stats.disconnects += 1;
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            },
+            Err(_) => break
+        };
+        let (mut write, mut read) = ws.split();
+
+        // JSON-RPC id => subscription kind, then subscription id => subscription kind
+        let mut pending: HashMap<u64, String> = HashMap::new();
+        let mut active: HashMap<String, String> = HashMap::new();
+        for (i, kind) in kinds.iter().enumerate() {
+            let params = match kind.as_str() {
+                "logs" => json!([kind, {}]),
+                _ => json!([kind])
+            };
+            let request = json!({"jsonrpc": "2.0", "id": i as u64 + 1, "method": "eth_subscribe", "params": params});
+            if write.send(Message::Text(request.to_string())).await.is_ok() {
+                pending.insert(i as u64 + 1, kind.clone());
+            }
+        }
+
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            let wait = if pending.is_empty() { deadline - now } else { std::cmp::min(deadline - now, SUBSCRIBE_TIMEOUT) };
+            let text = match timeout(wait, read.next()).await {
+                Ok(Some(Ok(Message::Text(text)))) => text,
+                Ok(Some(Ok(Message::Close(_)))) | Ok(Some(Err(_))) | Ok(None) => {
+                    stats.disconnects += 1;
+                    break;
+                },
+                Ok(Some(Ok(_))) => continue, // pings are answered by tungstenite itself
+                Err(_) if Instant::now() >= deadline => break,
+                Err(_) => {
+                    // The node never confirmed the subscriptions
+                    warn!(log, "eth_subscribe not confirmed by {}", job.endpoint_url);
+                    stats.disconnects += 1;
+                    break;
+                }
+            };
+            let message: Value = match serde_json::from_str(text.as_str()) {
+                Ok(m) => m,
+                Err(_) => continue
+            };
+
+            // eth_subscribe confirmation
+            if let Some(kind) = message["id"].as_u64().and_then(|id| pending.remove(&id)) {
+                match message["result"].as_str() {
+                    Some(subscription_id) => {
+                        active.insert(subscription_id.to_string(), kind);
+                    },
+                    None => warn!(log, "eth_subscribe {} rejected by {}: {}", kind, job.endpoint_url, message["error"])
+                }
+                if pending.is_empty() {
+                    stats.setup_ms.push(setup.elapsed().as_secs_f64() * 1000.0);
+                }
+                continue;
+            }
+
+            // eth_subscription notification
+            let kind = match message["params"]["subscription"].as_str().and_then(|id| active.get(id)) {
+                Some(k) => k,
+                None => continue
+            };
+            record_notification(&mut stats, &mut seen, &mut last_head, kind, &message["params"]["result"]);
+        }
+        let _ = write.close().await;
+    }
+    stats
+}
+
+fn record_notification(
+    stats: &mut models::SubscriptionStats,
+    seen: &mut HashSet<String>,
+    last_head: &mut Option<u64>,
+    kind: &str,
+    result: &Value
+) {
+    let key = match kind {
+        "newHeads" => result["hash"].as_str().map(|h| h.to_string()),
+        "logs" => Some(format!("{}:{}:{}", result["transactionHash"], result["logIndex"], result["removed"])),
+        _ => result.as_str().map(|h| h.to_string())
+    };
+    if let Some(key) = key {
+        if !seen.insert(format!("{}:{}", kind, key)) {
+            stats.duplicates += 1;
+            return;
+        }
+    }
+    stats.notifications += 1;
+    if kind != "newHeads" {
+        return;
+    }
+
+    let number = result["number"].as_str().and_then(|n| u64::from_str_radix(n.trim_start_matches("0x"), 16).ok());
+    if let (Some(number), Some(last)) = (number, *last_head) {
+        // Lower or equal numbers are reorgs, not drops
+        if number > last + 1 {
+            stats.dropped += number - last - 1;
+        }
+    }
+    if number.is_some() && number > *last_head {
+        *last_head = number;
+    }
+    // Block timestamps have a 1s resolution, so delivery latency is only accurate to the second
+    let timestamp = result["timestamp"].as_str().and_then(|t| u64::from_str_radix(t.trim_start_matches("0x"), 16).ok());
+    if let (Some(timestamp), Ok(now)) = (timestamp, SystemTime::now().duration_since(UNIX_EPOCH)) {
+        stats.delivery_ms.push(now.as_secs_f64() * 1000.0 - (timestamp * 1000) as f64);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::record_notification;
+    use crate::models::SubscriptionStats;
+    use serde_json::json;
+    use std::collections::HashSet;
+
+
+    #[test]
+    fn test_new_heads_gaps_and_duplicates() {
+        let mut stats = SubscriptionStats::default();
+        let mut seen = HashSet::new();
+        let mut last_head = None;
+        for (number, hash) in [("0x10", "0xa"), ("0x11", "0xb"), ("0x11", "0xb"), ("0x14", "0xc")] {
+            record_notification(&mut stats, &mut seen, &mut last_head, "newHeads", &json!({"number": number, "hash": hash, "timestamp": "0x0"}));
+        }
+        assert_eq!(stats.notifications, 3);
+        assert_eq!(stats.duplicates, 1);
+        assert_eq!(stats.dropped, 2);
+        assert_eq!(last_head, Some(0x14));
+    }
+}
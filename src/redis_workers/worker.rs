@@ -1,151 +1,422 @@
-
-use redis::{AsyncCommands, RedisError, Client};
-use rsmq_async::{Rsmq, RsmqConnection, RsmqMessage};
 use serde_json;
-use slog::{info, Logger, warn, error};
-use tokio::{task::JoinError, time::Instant};
-use reqwest;
+use slog::{Logger, error};
+use tokio::time::{timeout_at, Instant};
+use crate::backend::Backend;
 use crate::models;
+use crate::redis_workers::{batch, budget::RequestBudget, config::WorkerConfig, failures, http_client::JobClient, preflight, subscriptions, ws_transport};
 use futures::{self};
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::sync::Arc;
+use std::time::Duration;
 
 
 pub async fn start_worker(
-    redis_options: rsmq_async::RsmqOptions,
+    backend: Backend,
     log: Logger,
-    fail_percentage_treshold: f64
-) -> () {
-    // Connect to Redis db needed to sync workers and to schedule jobs
-    let redis_client = Client::open(format!("redis://{}", redis_options.host.clone())).unwrap();
-    let mut rsmq = Rsmq::new_with_connection(redis_options.clone(), redis_client.get_async_connection().await.unwrap());
-    let mut redis_connection_manager = redis::aio::ConnectionManager::new(redis_client.clone()).await.unwrap();
-    
-    // Generate various payloads for the json-rpc requests that will be sent concurrently
-    let eth_rpc_methods = gen_eth_json_rpc_methods();
-    let btc_rpc_methods = gen_btc_json_rpc_methods();
+    worker_config: WorkerConfig
+) {
     loop {
-        // Redis-worker receives the new TodoJob through RSMQ from the web server (actix thread)
-        let rsmq_msg: Result<Option<RsmqMessage<String>>, _> = rsmq.receive_message("jobs_q", None).await;
-        let rsmq_msg = rsmq_msg.unwrap();
-        if rsmq_msg.is_none() {
-            continue;
+        // Redis-worker receives the new TodoJob through the JobQueue from the web server (actix thread)
+        let queued_job = match backend.queue.receive().await {
+            Ok(Some(q)) => q,
+            Ok(None) => continue,
+            Err(e) => {
+                error!(log, "Failed to receive job: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        let job_id = queued_job.id.as_str();
+        match backend.store.status(job_id).await {
+            Ok(Some(check_rps)) if check_rps < 0 => {},
+            // 0 => job already allocated, >0 => job finished
+            _ => continue
         }
-        let check_rps: Result<i64, RedisError> = redis_connection_manager.get(rsmq_msg.clone().unwrap().id).await;
-        if check_rps.is_err() {
+
+        // Set this job as allocated such that it guarantees only this worker will execute it
+        // Note: with Redis, the atomicity of this step is guaranteed by the SET command
+        if backend.store.set_status(job_id, 0).await.is_err() {
+            error!(log, "Failed to mark job {} as allocated", job_id);
+        }
+
+        let mut job: models::TodoJob = serde_json::from_str(queued_job.message.as_str()).unwrap();
+        if job.job_type() == models::JobType::Load && job.seed.is_none() {
+            job.seed = Some(rand::thread_rng().gen_range(0..models::MAX_SEED));
+        }
+        // The endpoints may resolve to other addresses than at submission time
+        if let Err(e) = check_target_urls(&job, &worker_config).await {
+            error!(log, "Job {} targets a forbidden endpoint: {}", job_id, e);
+            finish_job(&backend, job_id, &job, -2, 0, &log).await;
             continue;
         }
-        let check_rps = check_rps.unwrap();
-        if check_rps >= 0 { // 0 => job already allocated, >0 => job finished
+        if let Err(e) = load_credentials(&backend, &mut job, &worker_config).await {
+            error!(log, "Failed to load credentials of job {}: {}", job_id, e);
+            finish_job(&backend, job_id, &job, -2, 0, &log).await;
             continue;
         }
-        let rsmq_msg = rsmq_msg.unwrap();
-        let job_id = rsmq_msg.id.as_str();
+        let http = match JobClient::new(&job, &worker_config) {
+            Ok(h) => h,
+            Err(e) => {
+                error!(log, "Failed to build HTTP client for job {}: {}", job_id, e);
+                finish_job(&backend, job_id, &job, -2, 0, &log).await;
+                continue;
+            }
+        };
 
-        // Set this job as allocated such that it guarantees only this worker will execute it
-        // Note: the atomicity of this step is guaranteed by the SET command of Redis
-        let res: Result<String, RedisError> = redis_connection_manager.set(job_id, 0).await;
+        // No point in sending load to an endpoint that fails its chain's health probe
+        if !preflight::probe_endpoint(&http.client, &job, &worker_config, &log).await {
+            finish_job(&backend, job_id, &job, -2, 0, &log).await;
+            continue;
+        }
 
-        // These will handle the concurrent tasks launched by the worker as requested in the TodoJob body
-        let mut concurrent_threads_handlers: Vec<actix_web::rt::task::JoinHandle<(f64, f64)>> = Vec::new();
-        let job: models::TodoJob = serde_json::from_str(rsmq_msg.message.as_str()).unwrap();
-        
-        // Apply prority-based randomness to the payloads send by the concurrent threads
-        // in order to replicate a real-world scenario as precisely as possible
-        let mut rpc_payloads: Vec<&str> = Vec::with_capacity(job.duration as usize * 2000);
-        select_rpc_payloads(&mut rpc_payloads, &eth_rpc_methods, &btc_rpc_methods, &job.chain, log.clone());
-        
-        let client = reqwest::Client::new();
-        for _i in 0..job.num_threads {
-            let thread_log = log.clone();
-            let job = job.clone();
-            let client_thread = client.clone();
-            let thread_rpc_payloads = rpc_payloads.clone(); //TODO: Optimization needed. Pass &rpc_payloads to spawned threads without cloning (Node: see scoped threads/crossbeam crate)
-            let start = Instant::now();
-            concurrent_threads_handlers.push(
-                actix_web::rt::spawn( 
-                    async move {
-                        execute_job(&thread_log, &job, &start, &client_thread, &thread_rpc_payloads).await
-                    }
-                )
-            );
+        let budget = RequestBudget::new(job.max_requests);
+        let (mut stats, subscriptions, comparison) = match job.job_type() {
+            models::JobType::Load => {
+                let (stats, comparison) = run_load_jobs(&log, &job, &http, &worker_config, &budget).await;
+                (stats, None, comparison)
+            },
+            models::JobType::Subscription => {
+                let (stats, subscriptions) = subscriptions::run_job(&log, &job, &worker_config).await;
+                (stats, Some(subscriptions), None)
+            }
+        };
+        let mut job_result = build_job_result(&job, &mut stats, subscriptions);
+        job_result.comparison = comparison;
+        if backend.store.set_result(job_id, &job_result).await.is_err() {
+            error!(log, "Failed to store details of job {}", job_id);
         }
 
-        // Worker waits for TodoJob's num_threads to finish
-        let join_results = futures::future::join_all(concurrent_threads_handlers).await;
-        
         // Check if the fails treshold is exceeded and mark job as failed (-2) or successfull (measured rps)
-        let (exceeded_treshold, rps) = job_fails_exceed_treshold(join_results, fail_percentage_treshold, log.clone());
-        if exceeded_treshold {
-            let res: Result<String, RedisError> = redis_connection_manager.set(job_id, -2).await;
-        } else {
-            let res: Result<String, RedisError> = redis_connection_manager.set(job_id, rps).await;
+        let (exceeded_treshold, rps) = job_fails_exceed_treshold(&stats, worker_config.fail_percentage_treshold);
+        let status = if exceeded_treshold { -2 } else { rps };
+        let requests = match job.job_type() {
+            models::JobType::Load => budget.sent(),
+            models::JobType::Subscription => job_result.requests + job_result.cancelled
+        };
+        finish_job(&backend, job_id, &job, status, requests, &log).await;
+    }
+
+}
+
+async fn check_target_urls(
+    job: &models::TodoJob,
+    worker_config: &WorkerConfig
+) -> Result<(), String> {
+    for url in job.target_urls() {
+        worker_config.endpoint_policy.check_resolved(url).await?;
+    }
+    Ok(())
+}
+
+// Replaces the job's credentials reference with the decrypted credentials stored by the API
+async fn load_credentials(
+    backend: &Backend,
+    job: &mut models::TodoJob,
+    worker_config: &WorkerConfig
+) -> Result<(), String> {
+    let reference = match &job.credentials {
+        Some(r) => r,
+        None => return Ok(())
+    };
+    let sealed = match backend.store.credentials(reference.as_str()).await? {
+        Some(s) => s,
+        None => return Err(format!("No credentials stored for reference {}", reference))
+    };
+    let credentials = worker_config.secrets.open(sealed.as_str())?;
+    let credentials: models::JobCredentials = serde_json::from_slice(&credentials).map_err(|e| e.to_string())?;
+    job.restore_credentials(credentials);
+    Ok(())
+}
+
+// Stores the job's final status (-2 => errored, >=0 => rps), releases the client's usage
+// reservation keeping the requests sent, deletes its credentials and only then deletes the job from the JobQueue
+async fn finish_job(
+    backend: &Backend,
+    job_id: &str,
+    job: &models::TodoJob,
+    status: i64,
+    requests: u64,
+    log: &Logger
+) {
+    if backend.store.set_status(job_id, status).await.is_err() {
+        error!(log, "Failed to store result of job {}", job_id);
+    }
+    if let Some(client) = &job.client {
+        let active_job = job.reservation.as_ref().map_or(job_id, |reservation| reservation.id.as_str());
+        if backend.store.remove_active_job(client.as_str(), active_job).await.is_err() {
+            error!(log, "Failed to remove job {} from the client's active jobs", job_id);
         }
-        
-        // Only now we can delete the job from RSMQ
-        let res = rsmq.delete_message("jobs_q", job_id).await.unwrap();
+        // The API reserved max_requests when the job was submitted, the ones not sent are given back
+        let (day, requests) = match (&job.reservation, job.max_requests) {
+            (Some(reservation), Some(max_requests)) => (reservation.day, requests as i64 - max_requests as i64),
+            _ => (models::ClientUsage::today(), requests as i64)
+        };
+        if backend.store.add_daily_requests(client.as_str(), day, requests).await.is_err() {
+            error!(log, "Failed to count the requests of job {}", job_id);
+        }
+    }
+    if let Some(reference) = &job.credentials {
+        if backend.store.delete_credentials(reference.as_str()).await.is_err() {
+            error!(log, "Failed to delete credentials of job {}", job_id);
+        }
+    }
+    if backend.queue.delete(job_id).await.is_err() {
+        error!(log, "Failed to delete job {} from the queue", job_id);
+    }
+}
+
+fn build_job_result(
+    job: &models::TodoJob,
+    stats: &mut models::ThreadStats,
+    subscriptions: Option<models::SubscriptionResult>
+) -> models::JobResult {
+    let batches = match job.batch_size {
+        Some(batch_size) if batch_size > 1 => Some(models::BatchResult {
+            batch_size,
+            batches: stats.batches,
+            fails: stats.batch_fails,
+            batches_per_second: stats.batches as f64 / stats.duration_s,
+            calls_per_second: (stats.ok_s + stats.fails) as f64 / stats.duration_s
+        }),
+        _ => None
+    };
+    models::JobResult {
+        transport: job.transport(),
+        requests: stats.ok_s + stats.fails,
+        fails: stats.fails,
+        errors: stats.errors.clone(),
+        cancelled: stats.cancelled,
+        duration_s: stats.duration_s,
+        requests_per_second: (stats.ok_s + stats.fails) as f64 / stats.duration_s,
+        latency_ms: models::LatencyStats::from_samples(&mut stats.latencies_ms),
+        client: job.client.clone(),
+        seed: job.seed,
+        connections_opened: stats.connections_opened,
+        batches,
+        subscriptions,
+        comparison: None
     }
+}
 
+// Runs the job against its endpoint_url and then, with the very same payload sequence,
+// against each of its compare_endpoints. Returns the endpoint_url's stats and the compared results
+async fn run_load_jobs(
+    log: &Logger,
+    job: &models::TodoJob,
+    http: &JobClient,
+    worker_config: &WorkerConfig,
+    budget: &RequestBudget
+) -> (models::ThreadStats, Option<Vec<models::EndpointResult>>) {
+    // Pre-flight: collect real recent blocks, txs and addresses from the target node
+    // and generate the payloads for the json-rpc requests that will be sent concurrently
+    let seed = match job.preflight_seeding() {
+        true => preflight::seed_from_endpoint(&http.client, job, log).await,
+        false => models::ChainSeed::default()
+    };
+    let rpc_methods = job.chain.profile().workload(&seed, job);
+    
+    // Apply prority-based randomness to the payloads send by the concurrent threads
+    // in order to replicate a real-world scenario as precisely as possible
+    // Each slot shares its method's payload, the sequence holds duration * 2000 of them
+    let mut rpc_payloads: Vec<Arc<models::RpcPayload>> = Vec::with_capacity(job.duration as usize * 2000);
+    // ChaCha8 is a fixed algorithm, unlike StdRng, so a seed selects the same sequence across rand versions and platforms
+    let mut rng = ChaCha8Rng::seed_from_u64(job.seed.unwrap_or_default());
+    select_rpc_payloads(&mut rpc_payloads, &rpc_methods, &mut rng);
+    let rpc_payloads = Arc::new(rpc_payloads);
+
+    if job.compare_endpoints.is_none() {
+        return (run_load_job(log, job, http, worker_config, budget, rpc_payloads).await, None);
+    }
+    let mut targets: Vec<(models::TodoJob, JobClient)> = vec![(job.clone(), http.clone())];
+    for compared_job in job.compared_jobs() {
+        match JobClient::new(&compared_job, worker_config) {
+            Ok(compared_http) => targets.push((compared_job, compared_http)),
+            Err(e) => error!(log, "Failed to build HTTP client for compared endpoint {}: {}", compared_job.endpoint_url, e)
+        }
+    }
+    // Every endpoint gets the same part of the job's requests, the first one can't leave the others without any
+    let budgets = budget.split(targets.len() as u64);
+    let mut results = match job.compare_mode.unwrap_or(models::CompareMode::Sequential) {
+        models::CompareMode::Concurrent => futures::future::join_all(
+            targets.iter().zip(budgets.iter()).map(|((job, http), budget)| run_load_job(log, job, http, worker_config, budget, rpc_payloads.clone()))
+        ).await,
+        models::CompareMode::Sequential => {
+            let mut results = Vec::with_capacity(targets.len());
+            for ((job, http), budget) in targets.iter().zip(budgets.iter()) {
+                results.push(run_load_job(log, job, http, worker_config, budget, rpc_payloads.clone()).await);
+            }
+            results
+        }
+    };
+    let stats = results.remove(0);
+    let comparison = targets.iter().skip(1).zip(results).map(|((compared_job, _), mut compared_stats)| models::EndpointResult {
+        endpoint_url: compared_job.endpoint_url.clone(),
+        result: build_job_result(compared_job, &mut compared_stats, None)
+    }).collect();
+    (stats, Some(comparison))
+}
+
+async fn run_load_job(
+    log: &Logger,
+    job: &models::TodoJob,
+    http: &JobClient,
+    worker_config: &WorkerConfig,
+    budget: &RequestBudget,
+    rpc_payloads: Arc<Vec<Arc<models::RpcPayload>>>
+) -> models::ThreadStats {
+    // These will handle the concurrent tasks launched by the worker as requested in the TodoJob body
+    let mut concurrent_threads_handlers: Vec<actix_web::rt::task::JoinHandle<models::ThreadStats>> = Vec::new();
+
+    // All threads share the same deadline, measurements start once the warm-up is over
+    let start = Instant::now() + Duration::from_secs(job.warmup.unwrap_or(0) as u64);
+    // Connections opened by the pre-flight requests and during the warm-up don't count
+    let connections_before = {
+        let http = http.clone();
+        actix_web::rt::spawn(async move {
+            tokio::time::sleep_until(start).await;
+            http.connections_opened()
+        })
+    };
+    for _i in 0..job.num_threads {
+        let thread_log = log.clone();
+        let job = job.clone();
+        let http_thread = http.clone();
+        let thread_worker_config = worker_config.clone();
+        let thread_budget = budget.clone();
+        let thread_rpc_payloads = rpc_payloads.clone();
+        concurrent_threads_handlers.push(
+            actix_web::rt::spawn( 
+                async move {
+                    match job.transport() {
+                        models::Transport::Http if job.batch_size.unwrap_or(1) > 1 => batch::execute_job(&thread_log, &job, &start, &http_thread, &thread_budget, &thread_rpc_payloads).await,
+                        models::Transport::Http => execute_job(&thread_log, &job, &start, &http_thread, &thread_budget, &thread_rpc_payloads).await,
+                        models::Transport::Ws => ws_transport::execute_job(&thread_log, &job, &start, &thread_worker_config, &thread_budget, &thread_rpc_payloads).await
+                    }
+                }
+            )
+        );
+    }
+
+    // Worker waits for TodoJob's num_threads to finish
+    let join_results = futures::future::join_all(concurrent_threads_handlers).await;
+    
+    let mut stats = models::ThreadStats::default();
+    for res in join_results.into_iter().flatten() {
+        stats.merge(res);
+    }
+    stats.duration_s = start.elapsed().as_secs_f64();
+    if job.transport() == models::Transport::Http {
+        if let (Ok(Some(connections_before)), Some(connections_after)) = (connections_before.await, http.connections_opened()) {
+            stats.connections_opened = Some(connections_after - connections_before);
+        }
+    }
+    stats
 }
 
 async fn execute_job(
-    log: &Logger, 
+    _log: &Logger, 
     job: &models::TodoJob, 
     start: &Instant, 
-    client: &reqwest::Client,
-    rpc_payloads: &Vec<&'static str>
-) -> (f64, f64) {
-    let mut ok_s: f64 = 0.0;
-    let mut fails: f64 = 0.0;
+    http: &JobClient,
+    budget: &RequestBudget,
+    rpc_payloads: &[Arc<models::RpcPayload>]
+) -> models::ThreadStats {
+    let client = &http.client;
+    let profile = job.chain.profile();
+    let deadline = *start + Duration::from_secs(job.duration as u64);
+    let mut stats = models::ThreadStats::default();
+    let mut warming_up = Instant::now() < *start;
     let mut rpc_payload_index = 0;
     loop {
-        if start.elapsed().as_secs() >= job.duration as u64 {
+        if Instant::now() >= deadline {
             break;
         }
+        // Discards what was measured during the warm-up
+        if warming_up && Instant::now() >= *start {
+            stats = models::ThreadStats::default();
+            warming_up = false;
+        }
         // Basically turns rpc_payloads Vec into a circular list 
-        if rpc_payload_index == rpc_payloads.len() - 1 {
+        if rpc_payload_index == rpc_payloads.len() {
             rpc_payload_index = 0;
         }
-        let mut request = client.post(job.endpoint_url.clone())
-                                        .body(*rpc_payloads.get(rpc_payload_index).unwrap())
-                                        .header("Content-Type", "application/json");
-        if job.authorization.is_some() {
-            request = request.header("Authorization", job.clone().authorization.unwrap())
+        let request = match rpc_payloads[rpc_payload_index].as_ref() {
+            // Cloning the Bytes only counts a reference, the body isn't copied
+            models::RpcPayload::Post(body) => client.post(job.endpoint_url.clone())
+                                                    .body(body.clone())
+                                                    .header("Content-Type", "application/json"),
+            get => client.get(get.get_url(job).unwrap_or_default())
+        };
+        rpc_payload_index += 1;
+        let request = job.authenticate(request);
+        let _permit = match timeout_at(deadline, http.acquire()).await {
+            Ok(permit) => permit,
+            Err(_) => break
+        };
+        // The client's daily requests are used up
+        if !budget.take(1) {
+            break;
         }
-        match request.send().await {
+        let sent = Instant::now();
+        // The request is dropped, hence cancelled, when the deadline hits while it is in flight
+        let response = match timeout_at(deadline, request.send()).await {
+            Ok(response) => response,
+            Err(_) => {
+                stats.cancelled += 1;
+                break;
+            }
+        };
+        match response {
             Ok(response) => 
                                     {
-                                        if response.status().is_success() { 
-                                            ok_s += 1.0;
+                                        if !response.status().is_success() { 
+                                            stats.fails += 1; // counts "HTTP 429 - Too many requests in a given amount of time." errors
+                                            stats.errors.add(models::ErrorKind::HttpStatus, 1);
+                                        } else if profile.validates_response_body() {
+                                            match timeout_at(deadline, response.text()).await {
+                                                Ok(Ok(body)) if profile.response_is_ok(body.as_str()) => stats.ok_s += 1,
+                                                Ok(Ok(_)) => {
+                                                    stats.fails += 1;
+                                                    stats.errors.add(models::ErrorKind::JsonRpc, 1);
+                                                },
+                                                Ok(Err(e)) => {
+                                                    stats.fails += 1;
+                                                    stats.errors.add(failures::classify_request_error(&e), 1);
+                                                },
+                                                Err(_) => {
+                                                    stats.cancelled += 1;
+                                                    break;
+                                                }
+                                            }
                                         } else {
-                                            fails += 1.0; // counts "HTTP 429 - Too many requests in a given amount of time." errors
+                                            stats.ok_s += 1;
                                         }
+                                        stats.latencies_ms.push(sent.elapsed().as_secs_f64() * 1000.0);
                                     },
-            Err(_) => {
-                        fails += 1.0; // counts "No response -> connection closed" errors
+            Err(e) => {
+                        stats.fails += 1; // counts "No response -> connection closed" errors
+                        stats.errors.add(failures::classify_request_error(&e), 1);
                     }
         }
     }
     // return successful and failed requests for job
-    (ok_s, fails)
+    stats
 }
 
 
 fn job_fails_exceed_treshold(
-    results: Vec<Result<(f64, f64), JoinError>>,
-    fail_percentage_treshold: f64,
-    log: Logger
+    stats: &models::ThreadStats,
+    fail_percentage_treshold: f64
 ) -> (bool, i64) 
 {
-    let mut ok_s: f64 = 0.0;
-    let mut fails: f64 = 0.0;
-    for res in results {
-        if res.is_err() {
-            continue;
-        }
-        let res = res.unwrap();
-        ok_s += res.0;
-        fails += res.1;
+    // Nothing answered (e.g. every request cancelled at the deadline, or a quiet subscription):
+    // failed, a finished job's status of 0 would read as allocated to a redis-worker
+    if stats.ok_s == 0 {
+        return (true, stats.fails as i64);
     }
+    let ok_s = stats.ok_s as f64;
+    let fails = stats.fails as f64;
     let fails_percentage = fails/(ok_s+fails) * 100.0;
     if fails_percentage >= fail_percentage_treshold {
         return (true, fails.floor() as i64);
@@ -156,37 +427,24 @@ fn job_fails_exceed_treshold(
 
 
 fn select_rpc_payloads(
-    rpc_payloads: &mut Vec<&str>,
-    evm_rpc_methods: &Vec<models::JsonRpcMethod>,
-    btc_rpc_methods: &Vec<models::JsonRpcMethod>,
-    chain: &str,
-    log: Logger
-) -> () {
-    if chain.eq("BTC") {
-        for _i in 0..rpc_payloads.capacity() {
-            let index = select_index_using_weighted_cdf(btc_rpc_methods, log.clone());
-            if index < 0 {
-                continue;
-            }
-            let index = index as usize;
-            rpc_payloads.push(btc_rpc_methods.get(index).unwrap().payload);
-        }
-    }
-    if chain.eq("EVM") {
-        for _i in 0..rpc_payloads.capacity() {
-            let index = select_index_using_weighted_cdf(evm_rpc_methods, log.clone());
-            if index < 0 {
-                continue;
-            }
-            let index = index as usize;
-            rpc_payloads.push(evm_rpc_methods.get(index).unwrap().payload);
+    rpc_payloads: &mut Vec<Arc<models::RpcPayload>>,
+    rpc_methods: &[models::JsonRpcMethod],
+    rng: &mut ChaCha8Rng
+) {
+    let payloads: Vec<Arc<models::RpcPayload>> = rpc_methods.iter().map(|rpc_method| Arc::new(rpc_method.payload.clone())).collect();
+    for _i in 0..rpc_payloads.capacity() {
+        let index = select_index_using_weighted_cdf(rpc_methods, rng);
+        if index < 0 {
+            continue;
         }
+        let index = index as usize;
+        rpc_payloads.push(payloads[index].clone());
     }
 }
 
 fn select_index_using_weighted_cdf(
-    rpc_methods: &Vec<models::JsonRpcMethod>,
-    log: Logger
+    rpc_methods: &[models::JsonRpcMethod],
+    rng: &mut ChaCha8Rng
 ) -> i32 {
     // Compute cumulative weights
     let mut weights_sum = 0;
@@ -200,209 +458,52 @@ fn select_index_using_weighted_cdf(
         weights_sum += rpc_methods[_i].weight;
     }
     // Pick a random number in the range of cumutalive weights
-    let index = rand::thread_rng().gen_range(0..weights_sum);
+    let index = rng.gen_range(0..weights_sum);
 
     // Search rpc_method index corresponding to the previous index
-    for _i in 0..cdf_weights.len() {
-        if index <= cdf_weights[_i] {
-            return _i as i32;
-        }
+    match cdf_weights.iter().position(|cdf_weight| index < *cdf_weight) {
+        Some(i) => i as i32,
+        None => -1
     }
-    return -1;
 }
 
 
-fn gen_btc_json_rpc_methods() -> Vec<models::JsonRpcMethod> {
-    let mut btc_methods = Vec::<models::JsonRpcMethod>::new();
-    btc_methods.push(
-            models::JsonRpcMethod {
-                payload:
-                    r#"{
-                        "jsonrpc": "2.0",
-                        "id": "1",
-                        "method": "sendrawtransaction",
-                        "params": ["01000000010b4d12cf890540c116463510fa823188a648ce7539b6a9ceb454bfbe8da447d7230000006b48304502210095d4cf3d7dcffaf50354ad3fd6e909e6c81156ac8f26b4a972c178e1c6b886b802206c6d3287d2a1bd9aa9f16187bf49ec24581d2b471e222d24babfd511d83bf29601210242581ee416579a142b436a2ef5ef0e117941fe7a2998d2d34c9f476233080f48ffffffff02a6580100000000001976a91476c37e0cc46f856092164f2fad78dbfc7de8c87e88ac3fc30f000000000017a91422603b24d6bc97d390793ec58de38222fcccae328700000000"]
-                    }"#,
-                weight: 16
-            }   
-    );
-    btc_methods.push(
-        models::JsonRpcMethod {
-            payload:
-                r#"{
-                    "jsonrpc": "2.0",
-                    "id": "1",
-                    "method": "logging",
-                    "params": [["all"], ["libevent"]]
-                }"#,
-            weight: 252
-        }
-    );
-    btc_methods.push(
-        models::JsonRpcMethod {
-            payload:
-                r#"{
-                    "jsonrpc": "2.0",
-                    "id": "1",
-                    "method": "gettxout",
-                    "params": ["47df2d439a7f7156da11a01478ea921c9fabc0f55a9f901291dccc762b40a937", 1]
-                }"#,
-            weight: 255
-        }
-    );
-    btc_methods.push(
-        models::JsonRpcMethod {
-            payload:
-                r#"{
-                    "jsonrpc": "2.0",
-                    "id": "1",
-                    "method": "getblock", 
-                    "params": ["00000000c937983704a73af28acdec37b049d214adbda81d7e2a3dd146f6ed09"]
-                }"#,
-            weight: 333
-        }   
-    );
-    btc_methods.push(
-        models::JsonRpcMethod {
-            payload:
-                r#"{
-                    "jsonrpc": "2.0",
-                    "id": "1",
-                    "method": "getblockstats",
-                    "params": [103221, []]
-                }"#,
-            weight: 390
-        }
-    );
+#[cfg(test)]
+mod tests {
 
+    use super::{job_fails_exceed_treshold, select_rpc_payloads};
+    use crate::models::{JsonRpcMethod, RpcPayload, ThreadStats};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use std::sync::Arc;
 
 
-
-    btc_methods
-}
-
-
-fn gen_eth_json_rpc_methods() -> Vec<models::JsonRpcMethod> {
-    let mut eth_methods = Vec::<models::JsonRpcMethod>::new();
-    eth_methods.push(
-            models::JsonRpcMethod {
-                payload: 
-                    r#"{
-                        "jsonrpc": "2.0",
-                        "id": 1,
-                        "method": "eth_sendRawTransaction",
-                        "params": ["0xf86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"]
-                    }"#,
-                weight: 16
-            }
-    );
-    eth_methods.push(
-        models::JsonRpcMethod {
-            payload:
-                r#"{
-                    "jsonrpc": "2.0",
-                    "id": 1,
-                    "method": "eth_getCode",
-                    "params": ["0x5B56438000bAc5ed2c6E0c1EcFF4354aBfFaf889","latest"]
-                }"#,
-            weight: 88
-        }
-    );
-    eth_methods.push(
-        models::JsonRpcMethod {
-            payload:
-                r#"{
-                    "jsonrpc": "2.0",
-                    "id": 1,
-                    "method": "eth_getLogs",
-                    "params": [{"address": "0xdAC17F958D2ee523a2206206994597C13D831ec7"}]
-                }"#,
-            weight: 252
-        }
-    );
-    eth_methods.push(
-        models::JsonRpcMethod {
-            payload:
-                r#"{
-                    "jsonrpc": "2.0",
-                    "id": 1,
-                    "method": "eth_getTransactionByHash",
-                    "params": ["0x04b713fdbbf14d4712df5ccc7bb3dfb102ac28b99872506a363c0dcc0ce4343c"]
-                }"#,
-            weight: 255
-        }
-    );
-    eth_methods.push(
-        models::JsonRpcMethod {
-            payload:
-                r#"{
-                    "jsonrpc": "2.0",
-                    "id": 1,
-                    "method": "eth_blockNumber",
-                    "params": []
-                }"#,
-            weight: 333
-        }
-    );
-    eth_methods.push(
-        models::JsonRpcMethod {
-            payload:
-                r#"{
-                    "jsonrpc": "2.0",
-                    "id": 1,
-                    "method": "eth_getTransactionCount",
-                    "params": ["0x8D97689C9818892B700e27F316cc3E41e17fBeb9", "latest"]
-                }"#,
-            weight: 390
-        }
-    );
-    eth_methods.push(
-        models::JsonRpcMethod {
-            payload:
-                r#"{
-                    "jsonrpc": "2.0",
-                    "id": 1,
-                    "method": "eth_getBlockByNumber",
-                    "params": ["0xc5043f",false]
-                }"#,
-            weight: 399
-        }
-    );
-    eth_methods.push(
-        models::JsonRpcMethod {
-            payload:
-                r#"{
-                    "jsonrpc": "2.0",
-                    "id": 1,
-                    "method": "eth_getBalance",
-                    "params": ["0x8D97689C9818892B700e27F316cc3E41e17fBeb9", "latest"]
-                }"#,
-            weight: 545
-        }
-    );
-    eth_methods.push(
-        models::JsonRpcMethod {
-            payload: 
-                r#"{
-                    "jsonrpc": "2.0",
-                    "id": 1,
-                    "method": "eth_getTransactionReceipt",
-                    "params": ["0x04b713fdbbf14d4712df5ccc7bb3dfb102ac28b99872506a363c0dcc0ce4343c"]
-                }"#,
-            weight: 607
-        }
-    );
-    eth_methods.push(
-        models::JsonRpcMethod {
-            payload:
-                r#"{
-                    "jsonrpc": "2.0",
-                    "id": 1,
-                    "method": "eth_call",
-                    "params": [{"from":null,"to":"0x6b175474e89094c44da98b954eedeac495271d0f","data":"0x70a082310000000000000000000000006E0d01A76C3Cf4288372a29124A26D4353EE51BE"}, "latest"]
-                }"#,
-            weight: 1928
-        }
-    );
-    return eth_methods;
+    #[test]
+    fn test_seeded_payload_selection_is_reproducible() {
+        let rpc_methods: Vec<JsonRpcMethod> = ["a", "b", "c"].iter().enumerate()
+            .map(|(i, body)| JsonRpcMethod { payload: RpcPayload::from(*body), weight: i as u32 + 1 })
+            .collect();
+        let select = |seed: u64| {
+            let mut rpc_payloads: Vec<Arc<RpcPayload>> = Vec::with_capacity(100);
+            select_rpc_payloads(&mut rpc_payloads, &rpc_methods, &mut ChaCha8Rng::seed_from_u64(seed));
+            rpc_payloads
+        };
+        assert_eq!(select(42), select(42));
+        assert_ne!(select(42), select(43));
+        // Recorded seeds must keep selecting the same sequence, whatever the rand version
+        let pinned: String = select(8731920458).iter().take(32).filter_map(|payload| payload.body()).collect();
+        assert_eq!(pinned, "ccccccacbccacaccbccccbccbbcbbbcc");
+        // The slots share their method's payload instead of copying it
+        let rpc_payloads = select(42);
+        let first_c = rpc_payloads.iter().find(|payload| payload.body() == Some("c")).unwrap();
+        assert!(rpc_payloads.iter().filter(|payload| payload.body() == Some("c")).all(|payload| Arc::ptr_eq(payload, first_c)));
+    }
+    #[test]
+    fn test_job_without_answers_is_failed() {
+        let stats = |ok_s: u64, fails: u64, cancelled: u64| ThreadStats { ok_s, fails, cancelled, ..Default::default() };
+        assert_eq!(job_fails_exceed_treshold(&stats(0, 0, 50), 5.0), (true, 0));
+        assert_eq!(job_fails_exceed_treshold(&stats(0, 0, 0), 5.0), (true, 0));
+        assert_eq!(job_fails_exceed_treshold(&stats(99, 1, 0), 5.0), (false, 99));
+        assert!(job_fails_exceed_treshold(&stats(90, 10, 0), 5.0).0);
+    }
 }
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use slog::{warn, Logger};
+use tokio::time::{timeout, timeout_at, Instant};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{client_async_tls_with_config, Connector, MaybeTlsStream, WebSocketStream};
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, http::{header::HeaderName, HeaderValue}, Message};
+
+use crate::models;
+use crate::redis_workers::{budget::RequestBudget, config::WorkerConfig, failures, http_client};
+
+const DEFAULT_PIPELINE_DEPTH: u32 = 8;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+
+// WS counterpart of worker::execute_job, each of the thread's connections
+// pipelines requests over the same circular list of payloads
+pub async fn execute_job(
+    log: &Logger,
+    job: &models::TodoJob,
+    start: &Instant,
+    worker_config: &WorkerConfig,
+    budget: &RequestBudget,
+    rpc_payloads: &[Arc<models::RpcPayload>]
+) -> models::ThreadStats {
+    // GET payloads (e.g. Cosmos LCD REST) have no JSON-RPC over WS equivalent
+    let bodies: Vec<&str> = rpc_payloads.iter().filter_map(|payload| payload.body()).collect();
+    let mut stats = models::ThreadStats::default();
+    if bodies.is_empty() {
+        return stats;
+    }
+
+    let connections = std::cmp::max(job.ws_connections.unwrap_or(1), 1) as usize;
+    let results = futures::future::join_all(
+        (0..connections).map(|i| run_connection(log, job, start, worker_config, budget, &bodies, i * bodies.len() / connections))
+    ).await;
+    for res in results {
+        stats.merge(res);
+    }
+    stats
+}
+
+// Sends a single request and waits for its response, used as the WS health probe
+pub async fn probe(
+    job: &models::TodoJob,
+    worker_config: &WorkerConfig,
+    body: &str
+) -> Option<String> {
+    let mut ws = connect(job, worker_config).await.ok()?;
+    ws.send(Message::Text(body.to_string())).await.ok()?;
+    loop {
+        match timeout(worker_config.request_timeout(job), ws.next()).await {
+            Ok(Some(Ok(Message::Text(text)))) => {
+                let _ = ws.close(None).await;
+                return Some(text);
+            },
+            Ok(Some(Ok(_))) => continue,
+            _ => return None
+        }
+    }
+}
+
+// Opens a WS connection to the job's endpoint within the connect timeout
+pub async fn connect(
+    job: &models::TodoJob,
+    worker_config: &WorkerConfig
+) -> Result<WsStream, models::ErrorKind> {
+    let mut url = reqwest::Url::parse(job.endpoint_url.as_str()).map_err(|_| models::ErrorKind::Other)?;
+    for (name, value) in job.query_params.iter().flatten() {
+        url.query_pairs_mut().append_pair(name, value);
+    }
+    let mut request = url.as_str().into_client_request().map_err(|e| failures::classify_ws_error(&e))?;
+    if let Some(authorization) = &job.authorization {
+        let authorization = HeaderValue::from_str(authorization).map_err(|_| models::ErrorKind::Other)?;
+        request.headers_mut().insert("Authorization", authorization);
+    }
+    for (name, value) in job.headers.iter().flatten() {
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| models::ErrorKind::Other)?;
+        let value = HeaderValue::from_str(value).map_err(|_| models::ErrorKind::Other)?;
+        request.headers_mut().insert(name, value);
+    }
+    // Without a client certificate tokio-tungstenite builds its default TLS connector
+    let connector = match http_client::client_identity(job, worker_config.client_cert_dir.as_deref()).map_err(|_| models::ErrorKind::Tls)? {
+        Some(identity) => {
+            let identity = native_tls::Identity::from_pkcs8(&identity.cert, &identity.key).map_err(|_| models::ErrorKind::Tls)?;
+            let tls = native_tls::TlsConnector::builder().identity(identity).build().map_err(|_| models::ErrorKind::Tls)?;
+            Some(Connector::NativeTls(tls))
+        },
+        None => None
+    };
+    let handshake = async {
+        let stream = connect_allowed(job, worker_config).await?;
+        client_async_tls_with_config(request, stream, None, connector).await.map_err(|e| failures::classify_ws_error(&e))
+    };
+    match timeout(worker_config.connect_timeout(job), handshake).await {
+        Ok(Ok((ws, _))) => Ok(ws),
+        Ok(Err(kind)) => Err(kind),
+        Err(_) => Err(models::ErrorKind::Timeout)
+    }
+}
+
+// The endpoint's host is resolved once and checked against the EndpointPolicy, the TCP connection
+// is opened to one of the allowed addresses so no second resolution can point it elsewhere
+async fn connect_allowed(job: &models::TodoJob, worker_config: &WorkerConfig) -> Result<TcpStream, models::ErrorKind> {
+    // Failed lookups are DNS errors, denied addresses fall into Other
+    let addrs = worker_config.endpoint_policy.resolve_allowed(job.endpoint_url.as_str()).await.map_err(|e| failures::classify_message(e.as_str()))?;
+    let stream = TcpStream::connect(addrs.as_slice()).await.map_err(|e| failures::classify_io_error(&e))?;
+    let _ = stream.set_nodelay(true);
+    Ok(stream)
+}
+
+async fn run_connection(
+    log: &Logger,
+    job: &models::TodoJob,
+    start: &Instant,
+    worker_config: &WorkerConfig,
+    budget: &RequestBudget,
+    bodies: &[&str],
+    mut rpc_payload_index: usize
+) -> models::ThreadStats {
+    let profile = job.chain.profile();
+    let pipeline_depth = std::cmp::max(job.ws_pipeline_depth.unwrap_or(DEFAULT_PIPELINE_DEPTH), 1) as usize;
+    let deadline = *start + Duration::from_secs(job.duration as u64);
+    let request_timeout = worker_config.request_timeout(job);
+    let mut stats = models::ThreadStats::default();
+    let mut warming_up = Instant::now() < *start;
+    let mut next_id: u64 = 0;
+    let mut budget_used_up = false;
+
+    // Reconnects for as long as the job lasts, a dropped connection fails its in-flight requests
+    while Instant::now() < deadline && !budget_used_up {
+        // Discards the connection failures of the warm-up
+        if warming_up && Instant::now() >= *start {
+            stats = models::ThreadStats::default();
+            warming_up = false;
+        }
+        let ws = match timeout_at(deadline, connect(job, worker_config)).await {
+            Ok(Ok(ws)) => ws,
+            Ok(Err(kind)) => {
+                stats.fails += 1; // counts "connection refused / handshake failed" errors
+                stats.errors.add(kind, 1);
+                // Waits before reconnecting, as subscriptions do, instead of hammering the endpoint (and its DNS)
+                tokio::time::sleep_until(std::cmp::min(Instant::now() + Duration::from_secs(1), deadline)).await;
+                continue;
+            },
+            Err(_) => break
+        };
+        let (mut write, mut read) = ws.split();
+        // JSON-RPC id => time the request was sent
+        let mut in_flight: HashMap<u64, Instant> = HashMap::new();
+        loop {
+            // Hard stop, the responses still in flight are not waited for
+            let now = Instant::now();
+            if now >= deadline {
+                stats.cancelled += in_flight.len() as u64;
+                break;
+            }
+            if warming_up && now >= *start {
+                stats = models::ThreadStats::default();
+                warming_up = false;
+            }
+            // Keep the pipeline full until the job's duration is over or the client's daily requests are used up
+            while in_flight.len() < pipeline_depth && !budget_used_up {
+                if !budget.take(1) {
+                    budget_used_up = true;
+                    break;
+                }
+                // Basically turns bodies into a circular list
+                if rpc_payload_index >= bodies.len() {
+                    rpc_payload_index = 0;
+                }
+                let mut request: Value = match serde_json::from_str(bodies[rpc_payload_index]) {
+                    Ok(r) => r,
+                    Err(_) => json!({})
+                };
+                rpc_payload_index += 1;
+                next_id += 1;
+                request["id"] = json!(next_id);
+                if write.send(Message::Text(request.to_string())).await.is_err() {
+                    break;
+                }
+                in_flight.insert(next_id, Instant::now());
+            }
+            if in_flight.is_empty() && budget_used_up {
+                break;
+            }
+            // Each response has request_timeout to arrive, but never past the deadline
+            let wait = std::cmp::min(request_timeout, deadline - now);
+            let text = match timeout(wait, read.next()).await {
+                Ok(Some(Ok(Message::Text(text)))) => text,
+                Ok(Some(Ok(Message::Close(_)))) | Ok(Some(Err(_))) | Ok(None) => {
+                    stats.fails += in_flight.len() as u64; // counts "No response -> connection closed" errors
+                    stats.errors.add(models::ErrorKind::Other, in_flight.len() as u64);
+                    in_flight.clear();
+                    break;
+                },
+                Err(_) if Instant::now() >= deadline => continue,
+                Err(_) => {
+                    stats.fails += in_flight.len() as u64;
+                    stats.errors.add(models::ErrorKind::Timeout, in_flight.len() as u64);
+                    in_flight.clear();
+                    break;
+                },
+                Ok(Some(Ok(_))) => continue // pings are answered by tungstenite itself
+            };
+            let response: Value = match serde_json::from_str(text.as_str()) {
+                Ok(r) => r,
+                Err(_) => continue
+            };
+            let sent = match response["id"].as_u64().and_then(|id| in_flight.remove(&id)) {
+                Some(s) => s,
+                None => {
+                    warn!(log, "WS response with unknown id from {}", job.endpoint_url);
+                    continue;
+                }
+            };
+            // Responses to warm-up requests aren't measured
+            if sent < *start {
+                continue;
+            }
+            stats.latencies_ms.push(sent.elapsed().as_secs_f64() * 1000.0);
+            if !profile.validates_response_body() || profile.response_is_ok(text.as_str()) {
+                stats.ok_s += 1;
+            } else {
+                stats.fails += 1;
+                stats.errors.add(models::ErrorKind::JsonRpc, 1);
+            }
+        }
+        let _ = write.close().await;
+    }
+    stats
+}
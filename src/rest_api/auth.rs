@@ -0,0 +1,161 @@
+use crate::models::AppState;
+use crate::rest_api::errors::*;
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    web, Error, FromRequest, HttpMessage, HttpRequest
+};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+
+// Name of the client that sent the request, as registered with its API key
+#[derive(Clone, Debug)]
+pub struct ApiClient(pub String);
+
+// Handlers behind ApiKeyAuth get the authenticated client as an extractor
+impl FromRequest for ApiClient {
+    type Error = AppError;
+    type Future = Ready<Result<ApiClient, AppError>>;
+
+    fn from_request(request: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(request.extensions().get::<ApiClient>().cloned().ok_or(AppError {
+            message: "Unauthorized".to_string(),
+            cause: Some("Request not authenticated".to_string()),
+            error_type: AppErrorType::Unauthorized,
+            code: ErrorCode::Unauthorized
+        }))
+    }
+}
+
+// Parses Config's API_KEYS, "<client_name>:<api_key>,<client_name>:<api_key>"
+pub fn parse_api_keys(api_keys: &str) -> HashMap<String, String> {
+    api_keys.split(',')
+            .filter_map(|entry| entry.trim().split_once(':'))
+            .filter(|(client, key)| !client.is_empty() && !key.is_empty())
+            .map(|(client, key)| (key.to_string(), client.to_string()))
+            .collect()
+}
+
+// Rejects requests without a known API key, sent either as
+// "Authorization: Bearer <api_key>" or as "x-api-key: <api_key>"
+pub struct ApiKeyAuth;
+
+impl<S, B> Transform<S, ServiceRequest> for ApiKeyAuth
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = ApiKeyAuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ApiKeyAuthMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct ApiKeyAuthMiddleware<S> {
+    service: Rc<S>
+}
+
+impl<S, B> Service<ServiceRequest> for ApiKeyAuthMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        Box::pin(async move {
+            match authenticate(&request).await {
+                Ok(client) => {
+                    request.extensions_mut().insert(client);
+                    service.call(request).await.map(|response| response.map_into_left_body())
+                },
+                // Keeps the AppError in the response for the RequestIds middleware
+                Err(e) => Ok(request.error_response(e).map_into_right_body())
+            }
+        })
+    }
+}
+
+async fn authenticate(request: &ServiceRequest) -> Result<ApiClient, AppError> {
+    let state = match request.app_data::<web::Data<AppState>>() {
+        Some(s) => s,
+        None => return Err(AppError {
+            message: "Missing application state".to_string(),
+            cause: None,
+            error_type: AppErrorType::InternalServerError,
+            code: ErrorCode::InternalError
+        })
+    };
+    let sublog = state.log.new(o!(
+        "middleware" => "api_key_auth",
+    ));
+    let api_key = match api_key(request) {
+        Some(k) => k,
+        None => return Err(AppError {
+            message: "Unauthorized".to_string(),
+            cause: Some("API key not provided, use the Authorization: Bearer <api_key> or x-api-key headers".to_string()),
+            error_type: AppErrorType::Unauthorized,
+            code: ErrorCode::Unauthorized
+        }).map_err(log_warn(sublog))
+    };
+
+    // Keys from Config first, then the ones registered at runtime in the JobStore
+    if let Some(client) = state.api_keys.get(api_key.as_str()) {
+        return Ok(ApiClient(client.clone()));
+    }
+    match state.store.api_key_client(api_key.as_str()).await {
+        Ok(Some(client)) => Ok(ApiClient(client)),
+        Ok(None) => Err(AppError {
+            message: "Unauthorized".to_string(),
+            cause: Some("Unknown API key".to_string()),
+            error_type: AppErrorType::Unauthorized,
+            code: ErrorCode::Unauthorized
+        }).map_err(log_warn(sublog)),
+        Err(e) => Err(AppError {
+            message: "Failed to look up API key".to_string(),
+            cause: Some(e),
+            error_type: AppErrorType::InternalServerError,
+            code: ErrorCode::StoreUnavailable
+        }).map_err(log_error(sublog))
+    }
+}
+
+fn api_key(request: &ServiceRequest) -> Option<String> {
+    let headers = request.headers();
+    if let Some(key) = headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
+        return Some(key.trim().to_string());
+    }
+    headers.get("Authorization")
+           .and_then(|v| v.to_str().ok())
+           .and_then(|v| v.strip_prefix("Bearer "))
+           .map(|key| key.trim().to_string())
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::parse_api_keys;
+
+
+    #[test]
+    fn test_parse_api_keys() {
+        let api_keys = parse_api_keys("team-a:key-1, team-b:key-2,broken,:key-3");
+        assert_eq!(api_keys.len(), 2);
+        assert_eq!(api_keys["key-1"], "team-a");
+        assert_eq!(api_keys["key-2"], "team-b");
+    }
+}
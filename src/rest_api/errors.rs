@@ -1,7 +1,9 @@
 use actix_web::{error::ResponseError, http::StatusCode, HttpResponse};
-use serde::Serialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use reqwest::Error;
+use crate::rest_api::validation::FieldError;
 pub use slog::{error, warn, o, Logger};
 
 
@@ -31,8 +33,14 @@ pub fn log_warn(log: Logger) -> impl Fn(AppError) -> AppError {
 pub enum AppErrorType {
     NotFoundError,
     InternalServerError,
+    #[allow(dead_code)]
     NotImplemented,
-    BadRequest
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    TooManyRequests,
+    // 400 listing every invalid field of the request
+    InvalidFields(Vec<FieldError>)
 }
 
 #[derive(Debug)]
@@ -40,11 +48,12 @@ pub struct AppError {
     pub message: String,
     pub cause: Option<String>,
     pub error_type: AppErrorType,
+    pub code: ErrorCode,
 }
 
 impl AppError {
     pub fn message(&self) -> String {
-        match &*self {
+        match self {
             AppError {
                 message,
                 error_type: AppErrorType::NotFoundError,
@@ -54,6 +63,7 @@ impl AppError {
                 message,
                 cause,
                 error_type: AppErrorType::InternalServerError,
+                ..
             } => {
                 let mut msg = String::new();
                 msg.push_str(message.as_str());
@@ -61,12 +71,13 @@ impl AppError {
                     msg.push_str(" Cause: "); 
                     msg.push_str(cause.clone().unwrap().as_str()); 
                 }
-                return msg;
+                msg
             },
             AppError {
                 message,
                 cause,
                 error_type: AppErrorType::NotImplemented,
+                ..
             } => {
                 let mut msg = String::new();
                 msg.push_str(message.as_str());
@@ -74,12 +85,13 @@ impl AppError {
                     msg.push_str(" Cause: "); 
                     msg.push_str(cause.clone().unwrap().as_str()); 
                 }
-                return msg;
+                msg
             },
             AppError {
                 message,
                 cause,
-                error_type: AppErrorType::BadRequest,
+                error_type: AppErrorType::BadRequest | AppErrorType::Unauthorized | AppErrorType::Forbidden | AppErrorType::TooManyRequests,
+                ..
             } => {
                 let mut msg = String::new();
                 msg.push_str(message.as_str());
@@ -87,7 +99,15 @@ impl AppError {
                     msg.push_str(" Cause: "); 
                     msg.push_str(cause.clone().unwrap().as_str()); 
                 }
-                return msg;
+                msg
+            },
+            AppError {
+                message,
+                error_type: AppErrorType::InvalidFields(fields),
+                ..
+            } => {
+                let fields: Vec<String> = fields.iter().map(|f| format!("{}: {}", f.field, f.message)).collect();
+                format!("{} Cause: {}", message, fields.join(", "))
             }
         }
     }
@@ -98,7 +118,8 @@ impl From<Error> for AppError {
         AppError {
             message: error.to_string(), 
             cause: None,
-            error_type: AppErrorType::InternalServerError
+            error_type: AppErrorType::InternalServerError,
+            code: ErrorCode::InternalError
         }
     }
 }
@@ -109,44 +130,88 @@ impl fmt::Display for AppError {
     }
 }
 
-#[derive(Serialize)]
+// Machine-readable code of an AppError, stable across releases unlike its message
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    // The request body isn't valid JSON
+    InvalidJson,
+    // The job's chain is unknown, the other invalid fields are reported as well
+    InvalidChain,
+    InvalidFields,
+    Unauthorized,
+    QuotaExceeded,
+    EndpointNotAllowed,
+    JobNotFound,
+    // The job couldn't be sent to the workers
+    QueueUnavailable,
+    // Redis couldn't be read or written
+    StoreUnavailable,
+    InternalError,
+    NotImplemented
+}
+
+#[derive(Serialize, JsonSchema)]
 pub struct AppErrorResponse {
-    pub error: String
+    pub error: String,
+    pub code: ErrorCode,
+    // Also sent as the x-request-id response header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<ErrorDetails>
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ErrorDetails {
+    // INVALID_CHAIN and INVALID_FIELDS only, every invalid field of the request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Vec<FieldError>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cause: Option<String>
+}
+
+impl AppError {
+    // The request id is only known to the RequestIds middleware, which renders AppErrors again with it
+    pub fn response(&self, request_id: Option<String>) -> HttpResponse {
+        let (error, details) = match &self.error_type {
+            AppErrorType::InvalidFields(fields) => (
+                self.message.clone(),
+                Some(ErrorDetails { fields: Some(fields.clone()), cause: None })
+            ),
+            _ => (
+                self.message(),
+                self.cause.clone().map(|cause| ErrorDetails { fields: None, cause: Some(cause) })
+            )
+        };
+        HttpResponse::build(self.status_code()).json(
+            AppErrorResponse {
+                error,
+                code: self.code,
+                request_id,
+                details
+            }
+        )
+    }
 }
 
 impl ResponseError for AppError {
-    fn error_response(&self) -> HttpResponse {
+    fn status_code(&self) -> StatusCode {
         match self.error_type {
-            AppErrorType::NotFoundError => {
-                HttpResponse::build(StatusCode::NOT_FOUND).json(
-                    AppErrorResponse {
-                        error: self.message()
-                    }
-                )
-            },
-            AppErrorType::InternalServerError => {
-                HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR).json(
-                    AppErrorResponse {
-                        error: self.message()
-                    }
-                )
-            },
-            AppErrorType::NotImplemented => {
-                HttpResponse::build(StatusCode::NOT_IMPLEMENTED).json(
-                    AppErrorResponse {
-                        error: self.message()
-                    }
-                )
-            },
-            AppErrorType::BadRequest => {
-                HttpResponse::build(StatusCode::BAD_REQUEST).json(
-                    AppErrorResponse {
-                        error: self.message()
-                    }
-                )
-            }
+            AppErrorType::NotFoundError => StatusCode::NOT_FOUND,
+            AppErrorType::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+            AppErrorType::NotImplemented => StatusCode::NOT_IMPLEMENTED,
+            AppErrorType::BadRequest => StatusCode::BAD_REQUEST,
+            AppErrorType::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppErrorType::Forbidden => StatusCode::FORBIDDEN,
+            AppErrorType::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            AppErrorType::InvalidFields(_) => StatusCode::BAD_REQUEST
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        self.response(None)
+    }
 }
 
 
@@ -154,7 +219,7 @@ impl ResponseError for AppError {
 #[cfg(test)]
 mod tests {
 
-    use super::{AppError, AppErrorType};
+    use super::{AppError, AppErrorType, ErrorCode};
 
 
     #[test]
@@ -163,6 +228,7 @@ mod tests {
             message: "The requested item was not found".to_string(),
             cause: None,
             error_type: AppErrorType::NotFoundError,
+            code: ErrorCode::JobNotFound,
         };
 
         assert_eq!(
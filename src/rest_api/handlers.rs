@@ -1,16 +1,20 @@
 use crate::models::*;
+use crate::rest_api::auth::ApiClient;
+use crate::rest_api::quotas;
+use crate::rest_api::validation::{self, FieldError};
 use crate::rest_api::errors::*;
 
 use actix_web::{get, post, web, HttpResponse, Responder, HttpRequest, http::StatusCode};
-use redis::{AsyncCommands, RedisError, Client};
-use rsmq_async::{Rsmq, RsmqConnection};
-use serde_json::json;
+use rand::{distributions::Alphanumeric, Rng};
 
+// Time a job may wait in the queue before its credentials expire
+const CREDENTIALS_TTL_MARGIN_S: u64 = 3600;
 
 
 #[get("/jobs/{job_id}")]
 pub async fn get_job(
     request: HttpRequest,
+    client: ApiClient,
     state: web::Data<AppState>
 ) -> Result<impl Responder, AppError> {
     // Get job_id from url
@@ -24,188 +28,403 @@ pub async fn get_job(
                 AppError {
                     message: "Job not found".to_string(),
                     cause: Some("job_id header not provided".to_string()),
-                    error_type: AppErrorType::NotFoundError
+                    error_type: AppErrorType::NotFoundError,
+                    code: ErrorCode::JobNotFound
                 }
             ).map_err(log_error(sublog));
         }
     };
 
-    // Connect to Redis db
-    let redis_client = match Client::open(format!("redis://{}", state.redis_options.host.as_str())) {
-        Ok(r) => r,
+    // Other clients' jobs don't exist for this client, it can neither see nor consume them
+    match state.store.owner(job_id).await {
+        Ok(Some(owner)) if owner == client.0 => (),
+        Ok(_) => {
+            let sublog = state.log.new(o!(
+                "handler" => "get_job",
+                "client" => client.0.clone()
+            ));
+            return Err(AppError {
+                message: "Job not found".to_string(),
+                cause:Some(format!("No job with id {}, or its result was already read", job_id)),
+                error_type:AppErrorType::NotFoundError,
+                code:ErrorCode::JobNotFound
+            }).map_err(log_warn(sublog));
+        },
         Err(e) => {
             let sublog = state.log.new(o!(
                 "handler" => "get_job",
             ));
             return Err(AppError {
-                message: format!("Failed to open RSMQ client -> Redis connection"),
-                cause:Some(e.to_string()),
-                error_type:AppErrorType::InternalServerError
+                message: "Failed to read job owner".to_string(),
+                cause:Some(e),
+                error_type:AppErrorType::InternalServerError,
+                code:ErrorCode::StoreUnavailable
             }).map_err(log_error(sublog));
         }
-    };
-    let mut redis_connection_manager = match redis::aio::ConnectionManager::new(redis_client.clone()).await {
-        Ok(r) => r,
+    }
+
+    // Search by job_id in the JobStore
+    // {job_id:job_rps}
+    let job_rps = match state.store.status(job_id).await {
+        Ok(Some(j)) => j,
+        Ok(None) => {
+            let sublog = state.log.new(o!(
+                "handler" => "get_job",
+            ));
+            return Err(AppError {
+                message: "Job not found".to_string(),
+                cause:Some(format!("No job with id {}, or its result was already read", job_id)),
+                error_type:AppErrorType::NotFoundError,
+                code:ErrorCode::JobNotFound
+            }).map_err(log_warn(sublog));
+        },
         Err(e) => {
             let sublog = state.log.new(o!(
                 "handler" => "get_job",
             ));
             return Err(AppError {
-                message: format!("Failed to create Redis ConnectionManager"),
-                cause:Some(e.to_string()),
-                error_type:AppErrorType::InternalServerError
+                message: "Failed to read job status".to_string(),
+                cause:Some(e),
+                error_type:AppErrorType::InternalServerError,
+                code:ErrorCode::StoreUnavailable
             }).map_err(log_error(sublog));
         }
     };
-
-    // Search by job_id in Redis db
-    // {job_id:job_rps}
-    let redis_response: Result<i64, RedisError> = redis_connection_manager.get(job_id).await;
-    if redis_response.is_err() {
-        return Ok(HttpResponse::with_body(StatusCode::NOT_FOUND, String::from("")));
-    }
-    let job_rps = redis_response.unwrap();
     
     // -1 => job is waiting to be scheduled, 
     // 0 => job is allocated to a redis-worker, 
     // >0 => job has been finished by a worker
     if job_rps == 0 || job_rps == -1 { 
-        return Ok(HttpResponse::with_body(StatusCode::OK, serde_json::to_string_pretty(&json!({"status":"PENDING"})).unwrap()));
+        let response = JobStatusResponse { status: JobStatus::Pending, rps: None, result: None };
+        return Ok(HttpResponse::with_body(StatusCode::OK, serde_json::to_string_pretty(&response).unwrap()));
     }
     // -2 => job's treshold of fails/requests exceeded, so job failed and dropped
     if job_rps == -2 { 
-        let res: Result<i32, RedisError> = redis_connection_manager.del(job_id).await;
-        if res.is_err() {
+        if state.store.delete_job(job_id).await.is_err() {
             let sublog = state.log.new(o!(
                 "handler" => "get_job",
             ));
-            error!(sublog, "Failed to delete job {} from Redis", job_id);
+            error!(sublog, "Failed to delete job {}", job_id);
         }
-        return Ok(HttpResponse::with_body(StatusCode::OK, serde_json::to_string_pretty(&json!({"status":"ERRORED"})).unwrap()));
+        let response = JobStatusResponse { status: JobStatus::Errored, rps: None, result: None };
+        return Ok(HttpResponse::with_body(StatusCode::OK, serde_json::to_string_pretty(&response).unwrap()));
     }
-    let res: Result<i32, RedisError> = redis_connection_manager.del(job_id).await;
-    if res.is_err() {
+    // Latency and request counters stored by the redis-worker next to the job's rps
+    let mut response = JobStatusResponse { status: JobStatus::Finished, rps: Some(job_rps), result: None };
+    response.result = state.store.result(job_id).await.ok().flatten();
+    if state.store.delete_job(job_id).await.is_err() {
         let sublog = state.log.new(o!(
             "handler" => "get_job",
         ));
-        error!(sublog, "Failed to delete job {} from Redis", job_id);
+        error!(sublog, "Failed to delete job {}", job_id);
     }
-    Ok(HttpResponse::with_body(StatusCode::OK, serde_json::to_string_pretty(&json!({"status":"FINISHED", "rps":job_rps})).unwrap()))
+    Ok(HttpResponse::with_body(StatusCode::OK, serde_json::to_string_pretty(&response).unwrap()))
 }
 
 #[post("/jobs")]
 pub async fn new_job(
     request_body: String,
+    client: ApiClient,
     state: web::Data<AppState>
 ) -> Result<impl Responder, AppError> {
     // Check request body corectness
-    match parse_request_body(&state, request_body.clone()) {
+    let mut todo_job = match parse_request_body(&state, request_body.clone()).await {
         Ok(j) => j,
         Err(e) => return Err(e)
     };
+    let sublog = state.log.new(o!(
+        "handler" => "new_job",
+        "client" => client.0.clone()
+    ));
+    quotas::check_job_limits(&state.quotas, &todo_job).map_err(log_warn(sublog.clone()))?;
     
-    // Redis & RSMQ setup
-    let redis_client = match Client::open(format!("redis://{}", state.redis_options.host.as_str())) {
-        Ok(r) => r,
+    let (reservation, max_requests) = quotas::reserve_usage(&state.quotas, state.store.as_ref(), client.0.as_str()).await.map_err(log_warn(sublog.clone()))?;
+    todo_job.max_requests = max_requests;
+    todo_job.reservation = Some(reservation.clone());
+    todo_job.client = Some(client.0.clone());
+    match queue_job(&state, &mut todo_job).await {
+        Ok(job_id) => Ok(HttpResponse::with_body(StatusCode::CREATED, serde_json::to_string_pretty(&NewJobResponse { id: job_id }).unwrap())),
         Err(e) => {
-            let sublog = state.log.new(o!(
-                "handler" => "new_job",
-            ));
-            return Err(AppError {
-                message: format!("Failed to open RSMQ client -> Redis connection"),
-                cause:Some(e.to_string()),
-                error_type:AppErrorType::InternalServerError
-            }).map_err(log_error(sublog));
+            // The job won't run, what it reserved of the client's quotas is given back
+            if quotas::release_usage(state.store.as_ref(), client.0.as_str(), &reservation, max_requests).await.is_err() {
+                error!(sublog, "Failed to release the job's usage reservation");
+            }
+            Err(e)
         }
-    };
-    let mut rsmq = Rsmq::new_with_connection(state.redis_options.clone(), redis_client.get_async_connection().await.unwrap());
-    let mut redis_connection_manager = match redis::aio::ConnectionManager::new(redis_client.clone()).await {
-        Ok(r) => r,
-        Err(e) => {
+    }
+}
+
+// Stores the job's credentials, sends it to the redis-workers and marks it as pending. Returns the job's id
+async fn queue_job(
+    state: &web::Data<AppState>,
+    todo_job: &mut TodoJob
+) -> Result<String, AppError> {
+    let client = todo_job.client.clone().unwrap_or_default();
+
+    // Credentials never travel through the JobQueue, they are stored encrypted in the JobStore
+    // and the TodoJob only carries a reference to them
+    let credentials = todo_job.take_credentials();
+    todo_job.credentials = None;
+    if !credentials.is_empty() {
+        let reference: String = rand::thread_rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect();
+        let sealed = match state.secrets.seal(serde_json::to_string(&credentials).unwrap().as_bytes()) {
+            Ok(s) => s,
+            Err(e) => {
+                let sublog = state.log.new(o!(
+                    "handler" => "new_job",
+                ));
+                return Err(AppError {
+                    message: "Failed to encrypt job credentials".to_string(),
+                    cause:Some(e),
+                    error_type:AppErrorType::InternalServerError,
+                    code:ErrorCode::InternalError
+                }).map_err(log_error(sublog));
+            }
+        };
+        // Kept for as long as the job may wait in the queue and run
+        let ttl_s = todo_job.total_duration() + CREDENTIALS_TTL_MARGIN_S;
+        if let Err(e) = state.store.set_credentials(reference.as_str(), sealed, ttl_s).await {
             let sublog = state.log.new(o!(
                 "handler" => "new_job",
             ));
             return Err(AppError {
-                message: format!("Failed to create Redis ConnectionManager"),
-                cause:Some(e.to_string()),
-                error_type:AppErrorType::InternalServerError
+                message: "Failed to store job credentials".to_string(),
+                cause:Some(e),
+                error_type:AppErrorType::InternalServerError,
+                code:ErrorCode::StoreUnavailable
             }).map_err(log_error(sublog));
         }
-    };
+        todo_job.credentials = Some(reference);
+    }
 
-    // Send the new TodoJob through RSMQ to the redis-workers
-    let job_id = match rsmq
-                                .send_message("jobs_q", request_body.clone(), Some(1))
-                                .await {
+    // Send the new TodoJob through the JobQueue to the redis-workers
+    let job_id = match state.queue.send(serde_json::to_string(&todo_job).unwrap()).await {
         Ok(j) => j,
         Err(e) => {
             let sublog = state.log.new(o!(
                 "handler" => "new_job",
             ));
+            if let Some(reference) = &todo_job.credentials {
+                if state.store.delete_credentials(reference.as_str()).await.is_err() {
+                    error!(sublog, "Failed to delete job credentials");
+                }
+            }
             return Err(AppError {
-                message: format!("Failed to send job through RSMQ"),
-                cause:Some(e.to_string()),
-                error_type:AppErrorType::InternalServerError
+                message: "Failed to send job".to_string(),
+                cause:Some(e),
+                error_type:AppErrorType::InternalServerError,
+                code:ErrorCode::QueueUnavailable
             }).map_err(log_error(sublog));
         }
     };
 
-    // if the job was sent successfully, mark it as waiting to be scheduled in the Redis db
-    let res: Result<String, RedisError> = redis_connection_manager.set(job_id.clone(), -1).await;
-    if res.is_err() {
+    // if the job was sent successfully, record its owner and mark it as waiting to be scheduled in the JobStore
+    if let Err(e) = state.store.set_owner(job_id.as_str(), client.as_str()).await {
+        let sublog = state.log.new(o!(
+            "handler" => "new_job",
+        ));
+        delete_queued_job(state, job_id.as_str(), todo_job, &sublog).await;
+        return Err(AppError {
+            message: "Failed to record job owner".to_string(),
+            cause:Some(e),
+            error_type:AppErrorType::InternalServerError,
+            code:ErrorCode::StoreUnavailable
+        }).map_err(log_error(sublog));
+    }
+    if let Err(e) = state.store.set_status(job_id.as_str(), -1).await {
         let sublog = state.log.new(o!(
             "handler" => "new_job",
         ));
+        delete_queued_job(state, job_id.as_str(), todo_job, &sublog).await;
         return Err(AppError {
-            message: format!("Failed to mark job as new in Redis"),
-            cause:Some(res.unwrap()),
-            error_type:AppErrorType::InternalServerError
+            message: "Failed to mark job as new".to_string(),
+            cause:Some(e),
+            error_type:AppErrorType::InternalServerError,
+            code:ErrorCode::StoreUnavailable
         }).map_err(log_error(sublog));
     }
-    Ok(HttpResponse::with_body(StatusCode::CREATED, serde_json::to_string_pretty(&json!({"id":job_id})).unwrap()))
+    Ok(job_id)
+}
+
+// A job that couldn't be marked as pending is never run, nor are its credentials used
+async fn delete_queued_job(
+    state: &web::Data<AppState>,
+    job_id: &str,
+    todo_job: &TodoJob,
+    sublog: &slog::Logger
+) {
+    if state.queue.delete(job_id).await.is_err() {
+        error!(sublog, "Failed to delete job {} from the queue", job_id);
+    }
+    if state.store.delete_job(job_id).await.is_err() {
+        error!(sublog, "Failed to delete job {}", job_id);
+    }
+    if let Some(reference) = &todo_job.credentials {
+        if state.store.delete_credentials(reference.as_str()).await.is_err() {
+            error!(sublog, "Failed to delete job credentials");
+        }
+    }
 }
 
-fn parse_request_body(
+async fn parse_request_body(
     state: &web::Data<AppState>, 
     request_body: String
 ) -> Result<TodoJob, AppError> {
-    match json::parse(request_body.as_str()) {
-        Ok(_) => {
-            match serde_json::from_str::<TodoJob>(request_body.as_str()) {
-                Ok(todo_job) => {
-                    if todo_job.chain.eq("BTC") || todo_job.chain.eq("EVM") {
-                        return Ok(todo_job);       
-                    }
-                    let sublog = state.log.new(o!(
-                        "handler" => "new_job > parse_request_body",
-                    ));
-                    return Err(AppError {
-                        message: format!("Unsupported chain"),
-                        cause:Some(format!("Chain field provided: {}", todo_job.chain)),
-                        error_type:AppErrorType::BadRequest
-                    }).map_err(log_error(sublog));
-                },
-                Err(e) => {
-                    let sublog = state.log.new(o!(
-                        "handler" => "new_job > parse_request_body",
-                    ));
-                    return Err(AppError {
-                        message: format!("Failed to deserialize TodoJob object"),
-                        cause:Some(e.to_string()),
-                        error_type:AppErrorType::BadRequest
-                    }).map_err(log_error(sublog));
-                }
-            }
-        },
+    let sublog = state.log.new(o!(
+        "handler" => "new_job > parse_request_body",
+    ));
+    let parsed = match validation::parse_job_body(request_body.as_str()) {
+        Ok(p) => p,
         Err(e) => {
-            let sublog = state.log.new(o!(
-                "handler" => "new_job > parse_request_body",
-            ));
             return Err(AppError {
-                message: format!("Failed to parse request body"),
-                cause:Some(e.to_string()),
-                error_type:AppErrorType::BadRequest
-            }).map_err(log_error(sublog));
+                message: "Failed to parse request body".to_string(),
+                cause:Some(e),
+                error_type:AppErrorType::BadRequest,
+                code:ErrorCode::InvalidJson
+            }).map_err(log_warn(sublog));
         }
+    };
+    let todo_job = match validation::parse_job(parsed, &state.job_limits) {
+        Ok(j) => j,
+        Err(errors) => return Err(invalid_job(errors)).map_err(log_warn(sublog))
+    };
+
+    // SSRF protection: the endpoints and the addresses they resolve to
+    // have to be allowed by the EndpointPolicy
+    for url in todo_job.target_urls() {
+        if let Err(cause) = state.endpoint_policy.check_resolved(url).await {
+            return Err(AppError {
+                message: "Endpoint not allowed".to_string(),
+                cause:Some(cause),
+                error_type:AppErrorType::Forbidden,
+                code:ErrorCode::EndpointNotAllowed
+            }).map_err(log_warn(sublog));
+        }
+    }
+    Ok(todo_job)
+}
+
+// An unknown chain is reported as INVALID_CHAIN, clients can't build a valid job without one
+fn invalid_job(errors: Vec<FieldError>) -> AppError {
+    let code = match errors.iter().any(|e| e.field == "chain") {
+        true => ErrorCode::InvalidChain,
+        false => ErrorCode::InvalidFields
+    };
+    AppError {
+        message: "Invalid job".to_string(),
+        cause:None,
+        error_type:AppErrorType::InvalidFields(errors),
+        code
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::backend::Backend;
+    use crate::config::Config;
+    use crate::endpoint_policy::EndpointPolicy;
+    use crate::models::{AppState, JobLimits, JobStatus, JobStatusResponse, NewJobResponse, Quotas};
+    use crate::redis_workers::{config::WorkerConfig, worker};
+    use crate::rest_api::auth;
+    use crate::secrets::SecretBox;
+    use actix_web::{test, web, App, HttpResponse, HttpServer};
+    use serde_json::json;
+    use std::time::Duration;
+
+
+    // Answers every JSON-RPC request as an EVM node would
+    fn start_rpc_node() -> String {
+        let server = HttpServer::new(|| App::new().default_service(web::to(|| async {
+            HttpResponse::Ok().content_type("application/json").body(r#"{"jsonrpc": "2.0", "id": 1, "result": "0x1"}"#)
+        }))).workers(1).bind(("127.0.0.1", 0)).unwrap();
+        let url = format!("http://{}/", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+        url
+    }
+
+    #[actix_web::test]
+    async fn test_job_is_run_on_the_memory_backend() {
+        let config: Config = serde_json::from_value(json!({
+            "server_host": "127.0.0.1",
+            "server_port": 0,
+            "rust_log": "info",
+            "queue_backend": "memory",
+            "num_of_workers": 1,
+            "fail_percentage_treshold": 5.0,
+            "secrets_key": "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=",
+            "api_keys": "team-a:key-a,team-b:key-b",
+            "quota_max_concurrent_jobs": 1,
+            "allowed_schemes": "http",
+            "allowed_hosts": "127.0.0.1/32"
+        })).unwrap();
+        let log = slog::Logger::root(slog::Discard, slog::o!());
+        let secrets = SecretBox::from_key(config.secrets_key.as_str()).unwrap();
+        let endpoint_policy = EndpointPolicy::from_config(&config).unwrap();
+        let backend = Backend::from_config(&config).await.unwrap();
+        let worker_config = WorkerConfig::from_config(&config, secrets.clone(), endpoint_policy.clone(), None);
+        let state = AppState {
+            log: log.clone(),
+            queue: backend.queue.clone(),
+            store: backend.store.clone(),
+            secrets,
+            api_keys: auth::parse_api_keys(config.api_keys.as_str()),
+            quotas: Quotas {
+                max_concurrent_jobs: config.quota_max_concurrent_jobs,
+                max_threads: config.quota_max_threads,
+                max_duration: config.quota_max_duration,
+                daily_requests: config.quota_daily_requests
+            },
+            endpoint_policy,
+            job_limits: JobLimits {
+                max_threads: config.max_threads,
+                max_duration: config.max_duration,
+                max_compare_endpoints: config.max_compare_endpoints,
+                max_batch_size: config.max_batch_size,
+                max_ws_connections: config.max_ws_connections,
+                max_ws_pipeline_depth: config.max_ws_pipeline_depth,
+                client_cert_dir: None
+            }
+        };
+        let app = test::init_service(App::new()
+            .app_data(web::Data::new(state))
+            .service(web::scope("/v1").wrap(auth::ApiKeyAuth).service(super::get_job).service(super::new_job))
+        ).await;
+        let post_job = |api_key: &str| test::TestRequest::post().uri("/v1/jobs")
+            .insert_header(("x-api-key", api_key))
+            .set_payload(json!({"chain": "EVM", "endpoint_url": start_rpc_node(), "num_threads": 1, "duration": 1}).to_string())
+            .to_request();
+        let get_job = |api_key: &str, job_id: &str| test::TestRequest::get().uri(format!("/v1/jobs/{}", job_id).as_str())
+            .insert_header(("x-api-key", api_key))
+            .to_request();
+
+        let response = test::call_service(&app, post_job("key-a")).await;
+        assert_eq!(response.status(), 201);
+        let job_id = test::read_body_json::<NewJobResponse, _>(response).await.id;
+        let status: JobStatusResponse = test::call_and_read_body_json(&app, get_job("key-a", job_id.as_str())).await;
+        assert_eq!(status.status, JobStatus::Pending);
+        // Other clients can't see the job, and can't consume its result
+        let response = test::call_service(&app, get_job("key-b", job_id.as_str())).await;
+        assert_eq!(response.status(), 404);
+        // The job holds team-a's only concurrent job until it's over
+        assert_eq!(test::call_service(&app, post_job("key-a")).await.status(), 429);
+
+        actix_web::rt::spawn(worker::start_worker(backend.clone(), log.clone(), worker_config));
+        let mut status = JobStatus::Pending;
+        for _ in 0..30 {
+            actix_web::rt::time::sleep(Duration::from_millis(500)).await;
+            let response: JobStatusResponse = test::call_and_read_body_json(&app, get_job("key-a", job_id.as_str())).await;
+            status = response.status;
+            if status != JobStatus::Pending {
+                let result = response.result.unwrap();
+                assert!(response.rps.unwrap() > 0);
+                assert!(result.requests > 0);
+                break;
+            }
+        }
+        assert_eq!(status, JobStatus::Finished);
+        // A result is only read once
+        assert_eq!(test::call_service(&app, get_job("key-a", job_id.as_str())).await.status(), 404);
+        assert_eq!(test::call_service(&app, post_job("key-a")).await.status(), 201);
+    }
+}
@@ -7,7 +7,7 @@ use actix_web::{get, web, HttpResponse, Responder};
 
 #[get("/health")]
 pub async fn health(
-    state: web::Data<AppState>
+    _state: web::Data<AppState>
 ) -> Result<impl Responder, AppError> {
     Ok(HttpResponse::Ok().finish())
 }
\ No newline at end of file
@@ -1,3 +1,8 @@
 pub mod health;
 pub mod handlers;
-pub mod errors;
\ No newline at end of file
+pub mod errors;
+pub mod auth;
+pub mod quotas;
+pub mod validation;
+pub mod openapi;
+pub mod request_id;
\ No newline at end of file
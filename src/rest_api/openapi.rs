@@ -0,0 +1,121 @@
+use crate::models::{AppState, JobStatusResponse, NewJobResponse, TodoJob};
+use crate::rest_api::errors::*;
+
+use actix_web::{get, web, HttpResponse, Responder};
+use schemars::gen::SchemaSettings;
+use serde_json::{json, Value};
+
+
+
+// OpenAPI 3 document of the /v1 API, the schemas are generated from the Rust types
+// so they can't drift from what the handlers accept and return
+pub fn openapi_document() -> Value {
+    let mut generator = SchemaSettings::openapi3().into_generator();
+    let todo_job = generator.subschema_for::<TodoJob>();
+    let new_job_response = generator.subschema_for::<NewJobResponse>();
+    let job_status_response = generator.subschema_for::<JobStatusResponse>();
+    let error_response = generator.subschema_for::<AppErrorResponse>();
+    // Every error response carries an x-request-id header and an AppErrorResponse body
+    let error = |description: &str| json!({
+        "description": description,
+        "headers": {"x-request-id": {"schema": {"type": "string"}}},
+        "content": {"application/json": {"schema": error_response}}
+    });
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Benchmarking service",
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "paths": {
+            "/v1/jobs": {
+                "post": {
+                    "summary": "Register a new job",
+                    "description": "`endpoint` and `threads` are accepted as aliases of `endpoint_url` and `num_threads`",
+                    "security": [{"ApiKey": []}, {"Bearer": []}],
+                    "requestBody": {
+                        "required": true,
+                        "content": {"application/json": {"schema": todo_job}}
+                    },
+                    "responses": {
+                        "201": {
+                            "description": "Job registered",
+                            "content": {"application/json": {"schema": new_job_response}}
+                        },
+                        "400": error("Invalid JSON, or invalid job with every invalid field listed in details.fields"),
+                        "401": error("Missing or unknown API key"),
+                        "403": error("Job above the client's quotas or endpoint not allowed"),
+                        "429": error("Client's concurrent jobs or daily requests exhausted"),
+                        "500": error("Internal error")
+                    }
+                }
+            },
+            "/v1/jobs/{job_id}": {
+                "get": {
+                    "summary": "Get a job's status, and its result once it is FINISHED",
+                    "security": [{"ApiKey": []}, {"Bearer": []}],
+                    "parameters": [{
+                        "name": "job_id",
+                        "in": "path",
+                        "required": true,
+                        "schema": {"type": "string"}
+                    }],
+                    "responses": {
+                        "200": {
+                            "description": "Job status",
+                            "content": {"application/json": {"schema": job_status_response}}
+                        },
+                        "401": error("Missing or unknown API key"),
+                        "404": error("Unknown job, or its result was already read"),
+                        "500": error("Internal error")
+                    }
+                }
+            },
+            "/health": {
+                "get": {
+                    "summary": "Health check",
+                    "responses": {"200": {"description": "Service is up"}}
+                }
+            }
+        },
+        "components": {
+            "schemas": generator.take_definitions(),
+            "securitySchemes": {
+                "ApiKey": {"type": "apiKey", "in": "header", "name": "x-api-key"},
+                "Bearer": {"type": "http", "scheme": "bearer"}
+            }
+        }
+    })
+}
+
+#[get("/v1/openapi.json")]
+pub async fn openapi(
+    _state: web::Data<AppState>
+) -> Result<impl Responder, AppError> {
+    Ok(HttpResponse::Ok().json(openapi_document()))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::openapi_document;
+
+
+    #[test]
+    fn test_openapi_document() {
+        let document = openapi_document();
+        assert!(document["paths"]["/v1/jobs"]["post"].is_object());
+        assert!(document["paths"]["/v1/jobs/{job_id}"]["get"].is_object());
+        assert_eq!(document["paths"]["/v1/jobs"]["post"]["requestBody"]["content"]["application/json"]["schema"]["$ref"], "#/components/schemas/TodoJob");
+
+        let todo_job = &document["components"]["schemas"]["TodoJob"];
+        assert!(todo_job["properties"]["endpoint_url"].is_object());
+        assert!(todo_job["properties"]["credentials"].is_null());
+        assert!(document["components"]["schemas"]["JobResult"].is_object());
+        assert!(document["components"]["schemas"]["Chain"].is_object());
+        assert!(document["components"]["schemas"]["ErrorCode"]["enum"].as_array().unwrap().contains(&"JOB_NOT_FOUND".into()));
+    }
+}
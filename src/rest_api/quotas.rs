@@ -0,0 +1,160 @@
+use crate::models::{ClientUsage, Quotas, TodoJob, UsageReservation};
+use crate::backend::JobStore;
+use crate::rest_api::errors::*;
+
+use rand::{distributions::Alphanumeric, Rng};
+
+
+// A single job can't exceed the per job limits, whatever the client's usage (403).
+// The limits hold for the job's compare_endpoints all together
+pub fn check_job_limits(quotas: &Quotas, todo_job: &TodoJob) -> Result<(), AppError> {
+    let threads = todo_job.total_threads();
+    if quotas.max_threads > 0 && threads > quotas.max_threads as u64 {
+        return Err(AppError {
+            message: "Quota exceeded".to_string(),
+            cause: Some(format!("{} threads are above the limit of {}", threads, quotas.max_threads)),
+            error_type: AppErrorType::Forbidden,
+            code: ErrorCode::QuotaExceeded
+        });
+    }
+    // The warm-up sends load too
+    let duration = todo_job.total_duration();
+    if quotas.max_duration > 0 && duration > quotas.max_duration as u64 {
+        return Err(AppError {
+            message: "Quota exceeded".to_string(),
+            cause: Some(format!("duration and warmup of {} seconds are above the limit of {} seconds", duration, quotas.max_duration)),
+            error_type: AppErrorType::Forbidden,
+            code: ErrorCode::QuotaExceeded
+        });
+    }
+    Ok(())
+}
+
+// Reserves one of the client's concurrent jobs and a share of the requests it has left today,
+// so concurrent submissions can't get past the quotas together, the client can try again later (429).
+// Without a concurrent jobs limit a job reserves every request left, otherwise its share of them
+// for the free job slots. Returns the reservation and the requests reserved, None when unlimited
+pub async fn reserve_usage(
+    quotas: &Quotas,
+    store: &dyn JobStore,
+    client: &str
+) -> Result<(UsageReservation, Option<u64>), AppError> {
+    let reservation = UsageReservation {
+        id: rand::thread_rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect(),
+        day: ClientUsage::today()
+    };
+    let active_jobs = match store.reserve_active_job(client, reservation.id.as_str(), quotas.max_concurrent_jobs).await.map_err(usage_error)? {
+        Some(active_jobs) => active_jobs,
+        None => return Err(AppError {
+            message: "Quota exceeded".to_string(),
+            cause: Some(format!("The limit of {} jobs pending or running is reached", quotas.max_concurrent_jobs)),
+            error_type: AppErrorType::TooManyRequests,
+            code: ErrorCode::QuotaExceeded
+        })
+    };
+    if quotas.daily_requests == 0 {
+        return Ok((reservation, None));
+    }
+    let share = match quotas.max_concurrent_jobs {
+        0 => 1,
+        max_concurrent_jobs => (max_concurrent_jobs - active_jobs + 1) as u64
+    };
+    let reserved = store.reserve_daily_requests(client, reservation.day, quotas.daily_requests, share).await;
+    match reserved {
+        Ok(Some(requests)) => Ok((reservation, Some(requests))),
+        Ok(None) => {
+            release_usage(store, client, &reservation, None).await.map_err(usage_error)?;
+            Err(AppError {
+                message: "Quota exceeded".to_string(),
+                cause: Some(format!("The daily limit of {} requests is reached", quotas.daily_requests)),
+                error_type: AppErrorType::TooManyRequests,
+                code: ErrorCode::QuotaExceeded
+            })
+        },
+        Err(e) => {
+            release_usage(store, client, &reservation, None).await.map_err(usage_error)?;
+            Err(usage_error(e))
+        }
+    }
+}
+
+// Gives back a reservation of a job that won't run, the reserved requests included
+pub async fn release_usage(
+    store: &dyn JobStore,
+    client: &str,
+    reservation: &UsageReservation,
+    reserved_requests: Option<u64>
+) -> Result<(), String> {
+    store.remove_active_job(client, reservation.id.as_str()).await?;
+    if let Some(requests) = reserved_requests {
+        store.add_daily_requests(client, reservation.day, -(requests as i64)).await?;
+    }
+    Ok(())
+}
+
+fn usage_error(e: String) -> AppError {
+    AppError {
+        message: "Failed to read client usage".to_string(),
+        cause: Some(e),
+        error_type: AppErrorType::InternalServerError,
+        code: ErrorCode::StoreUnavailable
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::{check_job_limits, release_usage, reserve_usage};
+    use crate::backend::{memory::MemoryBackend, JobStore};
+    use crate::models::{ClientUsage, CompareMode, Quotas, TodoJob};
+    use crate::rest_api::errors::AppErrorType;
+
+
+    #[test]
+    fn test_job_limits() {
+        let quotas = Quotas { max_threads: 50, max_duration: 300, ..Default::default() };
+        let job = |num_threads: u32, duration: u32| -> TodoJob {
+            serde_json::from_str(format!(r#"{{"chain": "EVM", "endpoint_url": "https://localhost", "num_threads": {}, "duration": {}}}"#, num_threads, duration).as_str()).unwrap()
+        };
+        assert!(check_job_limits(&quotas, &job(50, 300)).is_ok());
+        assert!(matches!(check_job_limits(&quotas, &job(51, 60)).unwrap_err().error_type, AppErrorType::Forbidden));
+        assert!(matches!(check_job_limits(&quotas, &job(10, 301)).unwrap_err().error_type, AppErrorType::Forbidden));
+        let mut warmed_up = job(10, 200);
+        warmed_up.warmup = Some(101);
+        assert!(matches!(check_job_limits(&quotas, &warmed_up).unwrap_err().error_type, AppErrorType::Forbidden));
+        assert!(check_job_limits(&Quotas::default(), &job(1000, 3600)).is_ok());
+        let mut compared = job(16, 101);
+        compared.compare_endpoints = Some(serde_json::from_str(r#"[{"endpoint_url": "https://a"}, {"endpoint_url": "https://b"}]"#).unwrap());
+        assert!(matches!(check_job_limits(&quotas, &compared).unwrap_err().error_type, AppErrorType::Forbidden));
+        compared.compare_mode = Some(CompareMode::Concurrent);
+        assert!(check_job_limits(&quotas, &compared).is_ok());
+        compared.num_threads = 17;
+        assert!(matches!(check_job_limits(&quotas, &compared).unwrap_err().error_type, AppErrorType::Forbidden));
+    }
+    #[tokio::test]
+    async fn test_usage_is_reserved_in_the_job_store() {
+        let quotas = Quotas { max_concurrent_jobs: 2, daily_requests: 1000, ..Default::default() };
+        let store = MemoryBackend::default();
+        let today = ClientUsage::today();
+
+        // The first job reserves its share of the 2 job slots, the second one what's left
+        let (first, first_requests) = reserve_usage(&quotas, &store, "team-a").await.unwrap();
+        assert_eq!(first_requests, Some(500));
+        let (second, second_requests) = reserve_usage(&quotas, &store, "team-a").await.unwrap();
+        assert_eq!(second_requests, Some(500));
+        assert!(matches!(reserve_usage(&quotas, &store, "team-a").await.unwrap_err().error_type, AppErrorType::TooManyRequests));
+
+        // A finished job gives back what it didn't send, a job that won't run all of it
+        store.remove_active_job("team-a", first.id.as_str()).await.unwrap();
+        store.add_daily_requests("team-a", first.day, 100 - 500).await.unwrap();
+        release_usage(&store, "team-a", &second, second_requests).await.unwrap();
+        // 100 requests were sent, both slots are free
+        assert_eq!(reserve_usage(&quotas, &store, "team-a").await.unwrap().1, Some(450));
+
+        store.add_daily_requests("team-a", today, 450).await.unwrap();
+        assert!(matches!(reserve_usage(&quotas, &store, "team-a").await.unwrap_err().error_type, AppErrorType::TooManyRequests));
+        assert_eq!(reserve_usage(&quotas, &store, "team-b").await.unwrap().1, Some(500));
+        assert_eq!(reserve_usage(&Quotas::default(), &store, "team-a").await.unwrap().1, None);
+    }
+}
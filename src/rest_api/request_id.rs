@@ -0,0 +1,121 @@
+use crate::rest_api::errors::*;
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderName, HeaderValue},
+    Error
+};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use rand::{distributions::Alphanumeric, Rng};
+use std::rc::Rc;
+
+// Taken from the request when the client sets it, and sent back with every response
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+
+// A client's own request id is only kept when it can't mess up the logs or the header
+fn is_valid_request_id(request_id: &str) -> bool {
+    !request_id.is_empty()
+        && request_id.len() <= 64
+        && request_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// Assigns every request an id, logged by the Logger middleware and sent back
+// in the response headers and in the body of the AppError responses
+pub struct RequestIds;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestIds
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequestIdsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestIdsMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct RequestIdsMiddleware<S> {
+    service: Rc<S>
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let request_id = match request.headers().get(REQUEST_ID_HEADER).and_then(|h| h.to_str().ok()) {
+            Some(r) if is_valid_request_id(r) => r.to_string(),
+            _ => rand::thread_rng().sample_iter(&Alphanumeric).take(16).map(char::from).collect()
+        };
+        let service = self.service.clone();
+        Box::pin(async move {
+            let response = service.call(request).await?;
+            // AppErrors are rendered without the request id, it's added here
+            let error_response = response.response()
+                                    .error()
+                                    .and_then(|e| e.as_error::<AppError>())
+                                    .map(|e| e.response(Some(request_id.clone())));
+            let mut response = match error_response {
+                Some(r) => response.into_response(r).map_into_right_body(),
+                None => response.map_into_left_body()
+            };
+            if let Ok(value) = HeaderValue::from_str(request_id.as_str()) {
+                response.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+            }
+            Ok(response)
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::{is_valid_request_id, RequestIds};
+    use crate::rest_api::errors::{AppError, AppErrorType, ErrorCode};
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+
+    #[actix_web::test]
+    async fn test_request_id_is_added_to_app_errors() {
+        let app = init_service(App::new().wrap(RequestIds).route("/", web::get().to(|| async {
+            Err::<HttpResponse, AppError>(AppError {
+                message: "Job not found".to_string(),
+                cause: None,
+                error_type: AppErrorType::NotFoundError,
+                code: ErrorCode::JobNotFound
+            })
+        }))).await;
+        let request = TestRequest::get().uri("/").insert_header(("x-request-id", "abc-123")).to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), 404);
+        assert_eq!(response.headers().get("x-request-id").unwrap(), "abc-123");
+        let body: serde_json::Value = read_body_json(response).await;
+        assert_eq!(body["code"], "JOB_NOT_FOUND");
+        assert_eq!(body["request_id"], "abc-123");
+    }
+
+    #[test]
+    fn test_request_id_validation() {
+        assert!(is_valid_request_id("3f2a9c1e-7b4d-4e5f-8a6b-1c2d3e4f5a6b"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("id\nforged log line"));
+        assert!(!is_valid_request_id(&"a".repeat(65)));
+    }
+}
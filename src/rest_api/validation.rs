@@ -0,0 +1,389 @@
+use crate::chains::Chain;
+use crate::models::{CompareEndpoint, CompareMode, HttpVersion, JobLimits, JobType, TodoJob, Transport, MAX_SEED};
+use crate::redis_workers::http_client;
+use crate::redis_workers::subscriptions::SUBSCRIPTION_KINDS;
+
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+
+// One invalid field of a job, all of them are returned together in the 400 response
+#[derive(Serialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String
+}
+
+impl FieldError {
+    fn new(field: &str, message: String) -> FieldError {
+        FieldError { field: field.to_string(), message }
+    }
+}
+
+// The request body has to be a JSON object, its fields are validated afterwards
+pub fn parse_job_body(request_body: &str) -> Result<serde_json::Value, String> {
+    let parsed: serde_json::Value = serde_json::from_str(request_body).map_err(|e| e.to_string())?;
+    if !parsed.is_object() {
+        return Err("The request body must be a JSON object".to_string());
+    }
+    Ok(parsed)
+}
+
+type TypeCheck = fn(&Value) -> Result<(), String>;
+type Placeholder = fn() -> Value;
+
+fn check_type<T: DeserializeOwned>(value: &Value) -> Result<(), String> {
+    serde_json::from_value::<T>(value.clone()).map(|_| ()).map_err(|e| e.to_string())
+}
+
+// The TodoJob's required fields, their alias and the placeholder of an invalid one
+const REQUIRED_FIELDS: [(&str, &str, TypeCheck, Placeholder); 3] = [
+    ("endpoint_url", "endpoint", check_type::<String>, || json!("https://localhost")),
+    ("num_threads", "threads", check_type::<u32>, || json!(1)),
+    ("duration", "duration", check_type::<u32>, || json!(1))
+];
+
+// The TodoJob's optional fields a client can set
+const OPTIONAL_FIELDS: [(&str, TypeCheck); 23] = [
+    ("authorization", check_type::<Option<String>>),
+    ("headers", check_type::<Option<HashMap<String, String>>>),
+    ("query_params", check_type::<Option<HashMap<String, String>>>),
+    ("client_cert_path", check_type::<Option<String>>),
+    ("client_key_path", check_type::<Option<String>>),
+    ("rest_endpoint_url", check_type::<Option<String>>),
+    ("transport", check_type::<Option<Transport>>),
+    ("ws_connections", check_type::<Option<u32>>),
+    ("ws_pipeline_depth", check_type::<Option<u32>>),
+    ("job_type", check_type::<Option<JobType>>),
+    ("subscriptions", check_type::<Option<Vec<String>>>),
+    ("batch_size", check_type::<Option<u32>>),
+    ("http_version", check_type::<Option<HttpVersion>>),
+    ("keep_alive", check_type::<Option<bool>>),
+    ("max_connections", check_type::<Option<u32>>),
+    ("tcp_nodelay", check_type::<Option<bool>>),
+    ("connect_timeout_ms", check_type::<Option<u64>>),
+    ("request_timeout_ms", check_type::<Option<u64>>),
+    ("warmup", check_type::<Option<u32>>),
+    ("compare_endpoints", check_type::<Option<Vec<CompareEndpoint>>>),
+    ("compare_mode", check_type::<Option<CompareMode>>),
+    ("seed", check_type::<Option<u64>>),
+    ("preflight_seeding", check_type::<Option<bool>>)
+];
+
+// The job out of a request body parsed by parse_job_body, or every field error of it.
+// The fields are type checked one by one before the deserialization, which would stop at the first
+// invalid one: an invalid field is reported and replaced by a placeholder, or dropped when optional
+pub fn parse_job(mut parsed: Value, limits: &JobLimits) -> Result<TodoJob, Vec<FieldError>> {
+    let mut errors: Vec<FieldError> = Vec::new();
+    let fields = match parsed.as_object_mut() {
+        Some(fields) => fields,
+        None => return Err(vec![FieldError::new("body", "must be a JSON object".to_string())])
+    };
+    if let Err(e) = check_chain(fields.get("chain").unwrap_or(&Value::Null)) {
+        errors.push(e);
+        fields.insert(String::from("chain"), json!(Chain::Evm));
+    }
+    for (field, alias, check, placeholder) in REQUIRED_FIELDS {
+        let value = match (fields.remove(field), fields.remove(alias)) {
+            (Some(_), Some(_)) if field != alias => Err(format!("must not be given together with {}", alias)),
+            (Some(value), _) | (None, Some(value)) => check(&value).map(|_| value),
+            (None, None) => Err("is required".to_string())
+        };
+        let value = value.unwrap_or_else(|message| {
+            errors.push(FieldError::new(field, message));
+            placeholder()
+        });
+        fields.insert(field.to_string(), value);
+    }
+    for (field, check) in OPTIONAL_FIELDS {
+        if let Some(Err(message)) = fields.get(field).map(check) {
+            errors.push(FieldError::new(field, message));
+            fields.remove(field);
+        }
+    }
+    let todo_job = match serde_json::from_value::<TodoJob>(parsed) {
+        Ok(j) => j,
+        Err(e) => {
+            errors.push(FieldError::new("body", e.to_string()));
+            return Err(errors);
+        }
+    };
+    // A field is only reported once, the placeholders aren't validated
+    for error in validate_job(&todo_job, limits) {
+        if !errors.iter().any(|e| e.field == error.field) {
+            errors.push(error);
+        }
+    }
+    match errors.is_empty() {
+        true => Ok(todo_job),
+        false => Err(errors)
+    }
+}
+
+// Every field error of the job, empty when the job is valid
+pub fn validate_job(todo_job: &TodoJob, limits: &JobLimits) -> Vec<FieldError> {
+    let mut errors: Vec<FieldError> = Vec::new();
+    if todo_job.num_threads == 0 || todo_job.num_threads > limits.max_threads {
+        errors.push(FieldError::new("num_threads", format!("must be between 1 and {}", limits.max_threads)));
+    }
+    if todo_job.duration == 0 || todo_job.duration > limits.max_duration {
+        errors.push(FieldError::new("duration", format!("must be between 1 and {} seconds", limits.max_duration)));
+    }
+    if todo_job.warmup.unwrap_or(0) > limits.max_duration {
+        errors.push(FieldError::new("warmup", format!("must be at most {} seconds", limits.max_duration)));
+    }
+    // Compare jobs multiply the threads (CONCURRENT) or the duration (SEQUENTIAL) by their endpoints
+    if todo_job.endpoints() > 1 && todo_job.total_threads() > limits.max_threads as u64 {
+        errors.push(FieldError::new("num_threads", format!("must be at most {} for all endpoints together", limits.max_threads)));
+    }
+    if todo_job.endpoints() > 1 && todo_job.total_duration() > limits.max_duration as u64 {
+        errors.push(FieldError::new("duration", format!("duration and warmup must be at most {} seconds for all endpoints together", limits.max_duration)));
+    }
+    if todo_job.seed.is_some_and(|seed| seed >= MAX_SEED) {
+        errors.push(FieldError::new("seed", "must be below 2^53".to_string()));
+    }
+    if let Err(message) = check_url(todo_job.endpoint_url.as_str(), &["http", "https", "ws", "wss"]) {
+        errors.push(FieldError::new("endpoint_url", message));
+    }
+    if let Some(rest_endpoint_url) = &todo_job.rest_endpoint_url {
+        if let Err(message) = check_url(rest_endpoint_url.as_str(), &["http", "https"]) {
+            errors.push(FieldError::new("rest_endpoint_url", message));
+        }
+    }
+    if todo_job.authorization.as_deref().map(|a| a.trim().is_empty()) == Some(true) {
+        errors.push(FieldError::new("authorization", "must not be empty".to_string()));
+    }
+    for (field, value, max) in [("ws_connections", todo_job.ws_connections, limits.max_ws_connections), ("ws_pipeline_depth", todo_job.ws_pipeline_depth, limits.max_ws_pipeline_depth)] {
+        if value.is_some_and(|value| value == 0 || value > max) {
+            errors.push(FieldError::new(field, format!("must be between 1 and {}", max)));
+        }
+    }
+    if todo_job.max_connections == Some(0) {
+        errors.push(FieldError::new("max_connections", "must be at least 1".to_string()));
+    }
+    for (field, value) in [("connect_timeout_ms", todo_job.connect_timeout_ms), ("request_timeout_ms", todo_job.request_timeout_ms)] {
+        if value == Some(0) {
+            errors.push(FieldError::new(field, "must be at least 1".to_string()));
+        }
+    }
+    if let Err(message) = check_ws_job(todo_job) {
+        errors.push(FieldError::new("transport", message));
+    }
+    if let Err(message) = check_batch_job(todo_job, limits) {
+        errors.push(FieldError::new("batch_size", message));
+    }
+    if let Err((field, message)) = check_subscription_job(todo_job) {
+        errors.push(FieldError::new(field, message));
+    }
+    if let Err((field, message)) = check_auth_options(todo_job, limits) {
+        errors.push(FieldError::new(field, message));
+    }
+    if let Err(message) = check_compare_job(todo_job, limits) {
+        errors.push(FieldError::new("compare_endpoints", message));
+    }
+    errors
+}
+
+// The chain is checked on its own so an unknown chain gets its own field error
+fn check_chain(chain: &serde_json::Value) -> Result<(), FieldError> {
+    match chain.as_str() {
+        Some(chain) => Chain::from_str(chain).map(|_| ()).map_err(|e| FieldError::new("chain", e)),
+        None => Err(FieldError::new("chain", format!("must be one of {}", Chain::ALL.map(|c| c.as_str()).join(", "))))
+    }
+}
+
+fn check_url(url: &str, schemes: &[&str]) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("is not a valid url: {}", e))?;
+    if !schemes.contains(&parsed.scheme()) {
+        return Err(format!("scheme must be one of {}", schemes.join(", ")));
+    }
+    if parsed.host_str().map(|h| h.is_empty()) != Some(false) {
+        return Err("has no host".to_string());
+    }
+    Ok(())
+}
+
+// Batches are sent as a single HTTP POST, which neither WS nor SUBSCRIPTION jobs do
+// A WS job only connects to ws:// and wss:// endpoints, compared endpoints included
+fn check_ws_job(todo_job: &TodoJob) -> Result<(), String> {
+    if todo_job.transport() != Transport::Ws {
+        return Ok(());
+    }
+    let compared_urls = todo_job.compare_endpoints.iter().flatten().map(|endpoint| endpoint.endpoint_url.as_str());
+    for url in std::iter::once(todo_job.endpoint_url.as_str()).chain(compared_urls) {
+        if !url.starts_with("ws://") && !url.starts_with("wss://") {
+            return Err(format!("WS requires a ws:// or wss:// endpoint, got {}", url));
+        }
+    }
+    Ok(())
+}
+
+fn check_batch_job(todo_job: &TodoJob, limits: &JobLimits) -> Result<(), String> {
+    match todo_job.batch_size {
+        None | Some(1) => Ok(()),
+        Some(0) => Err("must be at least 1".to_string()),
+        Some(batch_size) if batch_size > limits.max_batch_size => Err(format!("must be at most {}", limits.max_batch_size)),
+        Some(_) if todo_job.transport() != Transport::Http => Err("is only supported over HTTP".to_string()),
+        Some(_) if todo_job.job_type() != JobType::Load => Err("is only supported for LOAD jobs".to_string()),
+        Some(_) => Ok(())
+    }
+}
+
+// eth_subscribe is only available on EVM nodes and over WebSockets
+fn check_subscription_job(todo_job: &TodoJob) -> Result<(), (&'static str, String)> {
+    if todo_job.job_type() != JobType::Subscription {
+        return Ok(());
+    }
+    if todo_job.chain != Chain::Evm {
+        return Err(("job_type", format!("Subscriptions are only supported for EVM, chain field provided: {}", todo_job.chain)));
+    }
+    if todo_job.transport() != Transport::Ws {
+        return Err(("endpoint_url", "Subscriptions require a ws:// or wss:// endpoint_url".to_string()));
+    }
+    for kind in todo_job.subscriptions.iter().flatten() {
+        if !SUBSCRIPTION_KINDS.contains(&kind.as_str()) {
+            return Err(("subscriptions", format!("Unknown subscription: {}", kind)));
+        }
+    }
+    Ok(())
+}
+
+// Headers have to be valid HTTP headers, the mTLS certificate comes with its key
+// and both are files in the Config's CLIENT_CERT_DIR
+fn check_auth_options(todo_job: &TodoJob, limits: &JobLimits) -> Result<(), (&'static str, String)> {
+    let compared_headers = todo_job.compare_endpoints.iter().flatten().filter_map(|endpoint| endpoint.headers.as_ref());
+    for headers in todo_job.headers.iter().chain(compared_headers) {
+        for (name, value) in headers {
+            if reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_err() {
+                return Err(("headers", format!("Invalid header name: {}", name)));
+            }
+            if reqwest::header::HeaderValue::from_str(value).is_err() {
+                return Err(("headers", format!("Invalid value of header {}", name)));
+            }
+        }
+    }
+    if todo_job.client_cert_path.is_some() != todo_job.client_key_path.is_some() {
+        return Err(("client_cert_path", "client_cert_path and client_key_path must be provided together".to_string()));
+    }
+    for (field, path) in [("client_cert_path", &todo_job.client_cert_path), ("client_key_path", &todo_job.client_key_path)] {
+        if let Some(path) = path {
+            http_client::resolve_cert_path(limits.client_cert_dir.as_deref(), path.as_str()).map_err(|e| (field, e))?;
+        }
+    }
+    Ok(())
+}
+
+// Compared endpoints are sent the LOAD job's payloads over the same transport as endpoint_url,
+// each endpoint only once
+fn check_compare_job(todo_job: &TodoJob, limits: &JobLimits) -> Result<(), String> {
+    if todo_job.compare_endpoints.is_none() {
+        return Ok(());
+    }
+    if todo_job.job_type() != JobType::Load {
+        return Err("is only supported for LOAD jobs".to_string());
+    }
+    if todo_job.endpoints() - 1 > limits.max_compare_endpoints as u64 {
+        return Err(format!("must have at most {} endpoints", limits.max_compare_endpoints));
+    }
+    // Compared as parsed urls, so a differently cased host is the same endpoint
+    let normalized = |url: &str| reqwest::Url::parse(url).map(|url| url.to_string()).unwrap_or_else(|_| url.to_string());
+    let mut urls = vec![normalized(todo_job.endpoint_url.as_str())];
+    for endpoint in todo_job.compare_endpoints.iter().flatten() {
+        let url = normalized(endpoint.endpoint_url.as_str());
+        if urls.contains(&url) {
+            return Err(format!("Endpoint {} is compared more than once", endpoint.endpoint_url));
+        }
+        urls.push(url);
+    }
+    for compared_job in todo_job.compared_jobs() {
+        check_url(compared_job.endpoint_url.as_str(), &["http", "https", "ws", "wss"])
+            .map_err(|e| format!("{} {}", compared_job.endpoint_url, e))?;
+        if compared_job.transport() != todo_job.transport() {
+            return Err(format!("Compared endpoint {} doesn't use the job's transport", compared_job.endpoint_url));
+        }
+        // The LCD REST calls are sent to every endpoint or to none of them
+        match (&todo_job.rest_endpoint_url, &compared_job.rest_endpoint_url) {
+            (Some(_), Some(rest_endpoint_url)) => check_url(rest_endpoint_url.as_str(), &["http", "https"])
+                .map_err(|e| format!("{} {}", rest_endpoint_url, e))?,
+            (Some(_), None) => return Err(format!("Compared endpoint {} needs a rest_endpoint_url, as the job has one", compared_job.endpoint_url)),
+            (None, Some(_)) => return Err(format!("Compared endpoint {} has a rest_endpoint_url, but the job doesn't", compared_job.endpoint_url)),
+            (None, None) => {}
+        }
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::{check_chain, parse_job, parse_job_body, validate_job};
+    use crate::models::{JobLimits, TodoJob};
+    use serde_json::json;
+
+
+    #[test]
+    fn test_every_invalid_field_is_reported() {
+        let limits = JobLimits { max_threads: 100, max_duration: 600, max_compare_endpoints: 2, max_batch_size: 100, max_ws_connections: 4, max_ws_pipeline_depth: 32, client_cert_dir: None };
+        let job: TodoJob = serde_json::from_value(json!({
+            "chain": "EVM", "endpoint_url": "not a url", "num_threads": 1000000, "duration": 0, "authorization": "", "batch_size": 0,
+            "seed": 9007199254740992u64
+        })).unwrap();
+        let fields: Vec<String> = validate_job(&job, &limits).into_iter().map(|e| e.field).collect();
+        assert_eq!(fields, vec!["num_threads", "duration", "seed", "endpoint_url", "authorization", "batch_size"]);
+
+        let job: TodoJob = serde_json::from_value(json!({"chain": "EVM", "endpoint_url": "https://localhost/rpc", "num_threads": 100, "duration": 600})).unwrap();
+        assert!(validate_job(&job, &limits).is_empty());
+        let job: TodoJob = serde_json::from_value(json!({"chain": "EVM", "endpoint_url": "https://localhost/rpc", "num_threads": 1, "duration": 1, "transport": "WS"})).unwrap();
+        let fields: Vec<String> = validate_job(&job, &limits).into_iter().map(|e| e.field).collect();
+        assert_eq!(fields, vec!["transport"]);
+        let job: TodoJob = serde_json::from_value(json!({"chain": "EVM", "endpoint_url": "https://localhost/rpc", "num_threads": 1, "duration": 1, "batch_size": 4000000000u32})).unwrap();
+        let fields: Vec<String> = validate_job(&job, &limits).into_iter().map(|e| e.field).collect();
+        assert_eq!(fields, vec!["batch_size"]);
+        let job: TodoJob = serde_json::from_value(json!({"chain": "EVM", "endpoint_url": "wss://localhost/ws", "num_threads": 1, "duration": 1, "ws_connections": 5, "ws_pipeline_depth": 1000000})).unwrap();
+        let fields: Vec<String> = validate_job(&job, &limits).into_iter().map(|e| e.field).collect();
+        assert_eq!(fields, vec!["ws_connections", "ws_pipeline_depth"]);
+        assert!(check_chain(&json!("DOGE")).is_err());
+        assert!(check_chain(&json!(null)).is_err());
+        for body in ["[]", "1", "\"x\"", "null", "{"] {
+            assert!(parse_job_body(body).is_err());
+        }
+        assert!(parse_job_body("{}").is_ok());
+    }
+
+    #[test]
+    fn test_every_type_error_is_reported() {
+        let limits = JobLimits { max_threads: 100, max_duration: 600, max_compare_endpoints: 2, max_batch_size: 100, max_ws_connections: 4, max_ws_pipeline_depth: 32, client_cert_dir: None };
+        let fields = |body: serde_json::Value| -> Vec<String> { parse_job(body, &limits).unwrap_err().into_iter().map(|e| e.field).collect() };
+        assert_eq!(fields(json!({"num_threads": -1, "duration": 0, "endpoint_url": "x"})), vec!["chain", "num_threads", "duration", "endpoint_url"]);
+        assert_eq!(fields(json!({"chain": "EVM", "warmup": "10", "keep_alive": 1, "transport": "UDP"})), vec!["endpoint_url", "num_threads", "duration", "transport", "keep_alive", "warmup"]);
+        assert_eq!(fields(json!({"chain": "EVM", "endpoint": "https://localhost", "endpoint_url": "https://localhost", "threads": 1, "duration": 1})), vec!["endpoint_url"]);
+
+        let job = parse_job(json!({"chain": "SOL", "endpoint": "https://localhost/rpc", "threads": 10, "duration": 60, "seed": null}), &limits).unwrap();
+        assert_eq!((job.endpoint_url.as_str(), job.num_threads, job.duration), ("https://localhost/rpc", 10, 60));
+    }
+
+    #[test]
+    fn test_compare_endpoints_are_bounded() {
+        let limits = JobLimits { max_threads: 100, max_duration: 600, max_compare_endpoints: 2, max_batch_size: 100, max_ws_connections: 4, max_ws_pipeline_depth: 32, client_cert_dir: None };
+        let job = |compare_endpoints: &[&str], compare_mode: &str, num_threads: u32, duration: u32| -> TodoJob {
+            let compare_endpoints: Vec<_> = compare_endpoints.iter().map(|url| json!({"endpoint_url": url})).collect();
+            serde_json::from_value(json!({
+                "chain": "EVM", "endpoint_url": "https://a.omniatech.io/rpc", "num_threads": num_threads, "duration": duration,
+                "compare_endpoints": compare_endpoints, "compare_mode": compare_mode
+            })).unwrap()
+        };
+        let fields = |job: TodoJob| -> Vec<String> { validate_job(&job, &limits).into_iter().map(|e| e.field).collect() };
+        assert!(fields(job(&["https://b.omniatech.io/rpc", "https://c.omniatech.io/rpc"], "CONCURRENT", 33, 600)).is_empty());
+        assert!(fields(job(&["https://b.omniatech.io/rpc", "https://c.omniatech.io/rpc"], "SEQUENTIAL", 100, 200)).is_empty());
+        assert_eq!(fields(job(&["https://b.omniatech.io/rpc", "https://c.omniatech.io/rpc"], "CONCURRENT", 34, 600)), vec!["num_threads"]);
+        assert_eq!(fields(job(&["https://b.omniatech.io/rpc", "https://c.omniatech.io/rpc"], "SEQUENTIAL", 100, 201)), vec!["duration"]);
+        assert_eq!(fields(job(&["https://b.omniatech.io/rpc", "https://c.omniatech.io/rpc", "https://d.omniatech.io/rpc"], "SEQUENTIAL", 1, 1)), vec!["compare_endpoints"]);
+        assert_eq!(fields(job(&["https://A.omniatech.io/rpc"], "SEQUENTIAL", 1, 1)), vec!["compare_endpoints"]);
+        assert_eq!(fields(job(&["https://b.omniatech.io/rpc", "https://b.omniatech.io/rpc"], "SEQUENTIAL", 1, 1)), vec!["compare_endpoints"]);
+    }
+}
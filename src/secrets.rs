@@ -0,0 +1,67 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::fmt;
+
+// AES-GCM nonces are 96 bits
+const NONCE_LEN: usize = 12;
+
+
+// Encrypts the jobs' credentials before they are stored in Redis,
+// with the base64 encoded 256 bit SECRETS_KEY from the service's Config
+#[derive(Clone)]
+pub struct SecretBox {
+    cipher: Aes256Gcm
+}
+
+impl SecretBox {
+    pub fn from_key(key: &str) -> Result<SecretBox, String> {
+        let key = STANDARD.decode(key.trim()).map_err(|e| format!("SECRETS_KEY is not base64: {}", e))?;
+        let cipher = Aes256Gcm::new_from_slice(&key).map_err(|_| "SECRETS_KEY must be 32 bytes long".to_string())?;
+        Ok(SecretBox { cipher })
+    }
+
+    // base64(nonce || ciphertext)
+    pub fn seal(&self, plaintext: &[u8]) -> Result<String, String> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let mut sealed = nonce.to_vec();
+        sealed.extend(self.cipher.encrypt(&nonce, plaintext).map_err(|_| "Failed to encrypt".to_string())?);
+        Ok(STANDARD.encode(sealed))
+    }
+
+    pub fn open(&self, sealed: &str) -> Result<Vec<u8>, String> {
+        let sealed = STANDARD.decode(sealed).map_err(|e| format!("Sealed value is not base64: {}", e))?;
+        if sealed.len() < NONCE_LEN {
+            return Err("Sealed value is too short".to_string());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher.decrypt(Nonce::from_slice(nonce), ciphertext).map_err(|_| "Failed to decrypt".to_string())
+    }
+}
+
+// Never print the key
+impl fmt::Debug for SecretBox {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretBox(<redacted>)")
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::SecretBox;
+
+
+    #[test]
+    fn test_seal_and_open() {
+        let secrets = SecretBox::from_key("MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=").unwrap();
+        let sealed = secrets.seal(b"Bearer token").unwrap();
+        assert!(!sealed.contains("Bearer"));
+        assert_eq!(secrets.open(sealed.as_str()).unwrap(), b"Bearer token");
+
+        let other = SecretBox::from_key("ZmVkY2JhOTg3NjU0MzIxMGZlZGNiYTk4NzY1NDMyMTA=").unwrap();
+        assert!(other.open(sealed.as_str()).is_err());
+        assert!(SecretBox::from_key("c2hvcnQ=").is_err());
+    }
+}